@@ -0,0 +1,189 @@
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+use regex::Regex;
+use serde::{Deserialize, Deserializer};
+
+/// Kind of request that created the version being generated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Requester {
+    /// A patch build or GitHub pull request.
+    Patch,
+    /// A mainline commit, trigger or ad-hoc build.
+    Mainline,
+    /// A commit queue or merge queue build.
+    CommitQueue,
+}
+
+impl FromStr for Requester {
+    type Err = anyhow::Error;
+
+    /// Parse a requester, accepting the values of Evergreen's `requester` expansion.
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "patch" | "github_pr" => Ok(Self::Patch),
+            "mainline" | "commit" | "trigger" | "ad_hoc" | "github_tag" => Ok(Self::Mainline),
+            "commit_queue" | "github_merge_queue" => Ok(Self::CommitQueue),
+            _ => bail!("Unknown requester '{}'", s),
+        }
+    }
+}
+
+fn optional_regex<'de, D>(deserializer: D) -> std::result::Result<Option<Regex>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    Option::<String>::deserialize(deserializer)?
+        .map(|pattern| Regex::new(&pattern).map_err(serde::de::Error::custom))
+        .transpose()
+}
+
+/// Rule deciding if generated tasks matching it should be activated.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ActivationRule {
+    /// Requester the rule applies to, all requesters if not given.
+    pub requester: Option<Requester>,
+    /// Regex matched against the task name, without the `_gen` suffix.
+    #[serde(default, deserialize_with = "optional_regex")]
+    pub task: Option<Regex>,
+    /// Regex matched against the build variant name.
+    #[serde(default, deserialize_with = "optional_regex")]
+    pub build_variant: Option<Regex>,
+    /// Should matching tasks be activated.
+    pub activate: bool,
+}
+
+impl ActivationRule {
+    fn matches(&self, requester: Requester, task_name: Option<&str>, build_variant: &str) -> bool {
+        let requester_matches = self.requester.map(|r| r == requester).unwrap_or(true);
+        let task_matches = match (&self.task, task_name) {
+            (Some(pattern), Some(task_name)) => pattern.is_match(task_name),
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        let build_variant_matches = self
+            .build_variant
+            .as_ref()
+            .map(|p| p.is_match(build_variant))
+            .unwrap_or(true);
+        requester_matches && task_matches && build_variant_matches
+    }
+}
+
+/// Decides which generated tasks and build variants are activated.
+///
+/// Every rule whose selectors (`requester`, `task` and `build_variant`) all match applies,
+/// and later rules win. Nothing is activated if no rule applies.
+///
+/// ```yaml
+/// activation:
+///   - requester: patch
+///     activate: true
+///   - requester: mainline
+///     build_variant: "-required$"
+///     activate: true
+/// ```
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(transparent)]
+pub struct ActivationPolicy {
+    rules: Vec<ActivationRule>,
+}
+
+impl ActivationPolicy {
+    /// Create an activation policy from a list of rules.
+    pub fn new(rules: Vec<ActivationRule>) -> Self {
+        Self { rules }
+    }
+
+    fn decide(&self, requester: Requester, task_name: Option<&str>, build_variant: &str) -> bool {
+        self.rules
+            .iter()
+            .rev()
+            .find(|r| r.matches(requester, task_name, build_variant))
+            .map(|r| r.activate)
+            .unwrap_or(false)
+    }
+
+    /// Decide if the sub-tasks of a generated task should be activated.
+    ///
+    /// # Arguments
+    ///
+    /// * `requester` - Requester of the version being generated.
+    /// * `task_name` - Name of the task, without the `_gen` suffix.
+    /// * `build_variant` - Name of the build variant the task is generated for.
+    pub fn activate_task(
+        &self,
+        requester: Requester,
+        task_name: &str,
+        build_variant: &str,
+    ) -> bool {
+        self.decide(requester, Some(task_name), build_variant)
+    }
+
+    /// Decide if a generated build variant should be activated.
+    ///
+    /// Rules with a `task` selector do not apply to build variants.
+    ///
+    /// # Arguments
+    ///
+    /// * `requester` - Requester of the version being generated.
+    /// * `build_variant` - Name of the build variant.
+    pub fn activate_build_variant(&self, requester: Requester, build_variant: &str) -> bool {
+        self.decide(requester, None, build_variant)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    const POLICY: &str = r#"
+- requester: patch
+  activate: true
+- requester: patch
+  task: "^fuzzer"
+  activate: false
+- requester: mainline
+  build_variant: "-required$"
+  activate: true
+"#;
+
+    #[rstest]
+    #[case(Requester::Patch, "auth", "linux", true)]
+    #[case(Requester::Patch, "fuzzer_agg", "linux", false)]
+    #[case(Requester::Mainline, "auth", "linux-required", true)]
+    #[case(Requester::Mainline, "auth", "linux", false)]
+    #[case(Requester::CommitQueue, "auth", "linux-required", false)]
+    fn test_activate_task(
+        #[case] requester: Requester,
+        #[case] task_name: &str,
+        #[case] build_variant: &str,
+        #[case] expected: bool,
+    ) {
+        let policy: ActivationPolicy = serde_yaml::from_str(POLICY).unwrap();
+
+        assert_eq!(
+            policy.activate_task(requester, task_name, build_variant),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_task_rules_should_not_apply_to_build_variants() {
+        let policy: ActivationPolicy = serde_yaml::from_str(POLICY).unwrap();
+
+        assert!(policy.activate_build_variant(Requester::Patch, "linux"));
+        assert!(!policy.activate_build_variant(Requester::Mainline, "linux"));
+    }
+
+    #[rstest]
+    #[case("patch", Requester::Patch)]
+    #[case("github_pr", Requester::Patch)]
+    #[case("commit", Requester::Mainline)]
+    #[case("commit_queue", Requester::CommitQueue)]
+    fn test_requester_from_str(#[case] value: &str, #[case] expected: Requester) {
+        assert_eq!(Requester::from_str(value).unwrap(), expected);
+    }
+}
@@ -0,0 +1,117 @@
+use std::fs::File;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+use anyhow::{bail, Result};
+use flate2::{write::GzEncoder, Compression, GzBuilder};
+use tar::{Builder, Header};
+
+/// Modification time given to every archived file so archives are reproducible.
+const ARCHIVE_MTIME: u64 = 0;
+/// Permissions given to every archived file.
+const ARCHIVE_MODE: u32 = 0o644;
+
+/// Write a directory of generated configuration to a gzip-compressed tar file.
+///
+/// Files are archived under the name of the directory, in sorted order, and with fixed
+/// timestamps and ownership so the same configuration always produces the same archive.
+///
+/// # Arguments
+///
+/// * `config_dir` - Directory of generated configuration to archive.
+/// * `archive_path` - Location to write the archive to.
+pub fn write_config_archive(config_dir: &Path, archive_path: &Path) -> Result<()> {
+    if let Some(parent) = archive_path.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    let archive = File::create(archive_path)?;
+    build_config_archive(config_dir, archive)?;
+    Ok(())
+}
+
+/// Write a gzip-compressed tar of a directory of generated configuration to a writer.
+///
+/// # Arguments
+///
+/// * `config_dir` - Directory of generated configuration to archive.
+/// * `writer` - Writer to write the compressed archive to.
+pub fn build_config_archive<W: Write>(config_dir: &Path, writer: W) -> Result<W> {
+    let prefix = match config_dir.file_name() {
+        Some(name) => PathBuf::from(name),
+        None => bail!("Cannot archive '{}'", config_dir.display()),
+    };
+    let encoder: GzEncoder<W> = GzBuilder::new()
+        .mtime(ARCHIVE_MTIME as u32)
+        .write(writer, Compression::default());
+    let mut builder = Builder::new(encoder);
+
+    for file in list_files(config_dir)? {
+        let contents = std::fs::read(&file)?;
+        let mut header = Header::new_gnu();
+        header.set_size(contents.len() as u64);
+        header.set_mtime(ARCHIVE_MTIME);
+        header.set_mode(ARCHIVE_MODE);
+        header.set_uid(0);
+        header.set_gid(0);
+        header.set_cksum();
+        let name = prefix.join(file.strip_prefix(config_dir)?);
+        builder.append_data(&mut header, name, contents.as_slice())?;
+    }
+
+    Ok(builder.into_inner()?.finish()?)
+}
+
+/// List all files under a directory, recursively and in sorted order.
+fn list_files(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut entries: Vec<PathBuf> = std::fs::read_dir(dir)?
+        .map(|e| e.map(|e| e.path()))
+        .collect::<std::io::Result<_>>()?;
+    entries.sort();
+
+    let mut files = vec![];
+    for entry in entries {
+        if entry.is_dir() {
+            files.extend(list_files(&entry)?);
+        } else {
+            files.push(entry);
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use tar::Archive;
+
+    #[test]
+    fn test_archive_should_be_deterministic() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let config_dir = tmp_dir.path().join("generated_resmoke_config");
+        std::fs::create_dir_all(config_dir.join("nested")).unwrap();
+        std::fs::write(config_dir.join("b.yml"), "b").unwrap();
+        std::fs::write(config_dir.join("a.yml"), "a").unwrap();
+        std::fs::write(config_dir.join("nested").join("c.json"), "c").unwrap();
+
+        let first = build_config_archive(&config_dir, vec![]).unwrap();
+        let second = build_config_archive(&config_dir, vec![]).unwrap();
+
+        let mut archive = Archive::new(GzDecoder::new(first.as_slice()));
+        let names: Vec<String> = archive
+            .entries()
+            .unwrap()
+            .map(|e| e.unwrap().path().unwrap().display().to_string())
+            .collect();
+
+        assert_eq!(first, second);
+        assert_eq!(
+            names,
+            vec![
+                "generated_resmoke_config/a.yml",
+                "generated_resmoke_config/b.yml",
+                "generated_resmoke_config/nested/c.json",
+            ]
+        );
+    }
+}
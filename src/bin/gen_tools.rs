@@ -0,0 +1,107 @@
+use std::{collections::HashMap, path::PathBuf};
+use structopt::StructOpt;
+
+use mongo_task_gen::{
+    diff::{diff_configs, GeneratedConfigDir},
+    expansions::Expansions,
+    generator_config::GeneratorConfig,
+    get_project_config,
+    lint::lint_project,
+    resmoke::{ResmokeSuiteConfig, VersionCombinationConfig},
+};
+
+#[derive(Debug, StructOpt)]
+enum Opt {
+    /// Compare two directories of generated configuration.
+    Diff {
+        #[structopt(parse(from_os_str))]
+        before_dir: PathBuf,
+
+        #[structopt(parse(from_os_str))]
+        after_dir: PathBuf,
+
+        /// Json file mapping tests to their average runtime, used to predict runtime changes.
+        #[structopt(long, parse(from_os_str))]
+        test_runtimes: Option<PathBuf>,
+
+        /// Output format, either `text` or `json`.
+        #[structopt(long, default_value = "text")]
+        format: String,
+    },
+    /// Check the definitions of all generated tasks in a project.
+    Lint {
+        #[structopt(long, parse(from_os_str), default_value = "etc/evergreen.yml")]
+        evg_project_location: PathBuf,
+
+        /// Yaml file of expansions available to every build variant.
+        #[structopt(long, parse(from_os_str))]
+        expansion_file: Option<PathBuf>,
+
+        #[structopt(long, parse(from_os_str))]
+        version_combination_file: Option<PathBuf>,
+
+        /// Yaml file configuring generator functions and task overrides.
+        #[structopt(long, parse(from_os_str))]
+        generator_config_file: Option<PathBuf>,
+    },
+}
+
+fn main() {
+    let opt = Opt::from_args();
+
+    match opt {
+        Opt::Diff {
+            before_dir,
+            after_dir,
+            test_runtimes,
+            format,
+        } => {
+            let before = GeneratedConfigDir::load(&before_dir).unwrap();
+            let after = GeneratedConfigDir::load(&after_dir).unwrap();
+            let test_runtimes: Option<HashMap<String, f64>> = test_runtimes
+                .map(|f| serde_json::from_str(&std::fs::read_to_string(f).unwrap()).unwrap());
+
+            let config_diff = diff_configs(&before, &after, test_runtimes.as_ref());
+            match format.as_str() {
+                "json" => println!("{}", serde_json::to_string_pretty(&config_diff).unwrap()),
+                "text" => print!("{}", config_diff),
+                _ => {
+                    eprintln!("Unknown format '{}', expected one of: text, json", format);
+                    std::process::exit(1);
+                }
+            }
+        }
+        Opt::Lint {
+            evg_project_location,
+            expansion_file,
+            version_combination_file,
+            generator_config_file,
+        } => {
+            let evg_project = get_project_config(&evg_project_location).unwrap();
+            let expansions = expansion_file
+                .map(|f| Expansions::from_yaml_file(&f).unwrap())
+                .unwrap_or_default();
+            let version_combinations = version_combination_file
+                .map(|f| VersionCombinationConfig::from_yaml_file(&f).unwrap())
+                .unwrap_or_default();
+            let generator_config = generator_config_file
+                .map(|f| GeneratorConfig::from_yaml_file(&f).unwrap())
+                .unwrap_or_default();
+
+            let problems = lint_project(
+                &evg_project,
+                generator_config.generator_functions(),
+                &expansions,
+                &version_combinations,
+                ResmokeSuiteConfig::try_read_suite_config,
+            );
+            for problem in &problems {
+                println!("{}", problem);
+            }
+            if !problems.is_empty() {
+                eprintln!("Found {} problem(s) in generated tasks", problems.len());
+                std::process::exit(1);
+            }
+        }
+    }
+}
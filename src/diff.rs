@@ -0,0 +1,516 @@
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::Result;
+use serde::Serialize;
+use shrub_rs::models::{commands::EvgCommand, params::ParamValue, project::EvgProject};
+
+use crate::{
+    report::GENERATION_ARTIFACTS, resmoke::ResmokeSuiteConfig, split_tasks::misc_suite_name,
+};
+
+const MANIFEST_SUFFIX: &str = "_manifest";
+/// Suffix of misc suite files written before they were named per build variant.
+const MISC_SUFFIX: &str = "_misc";
+
+/// The contents of a directory of generated configuration.
+#[derive(Debug, Clone, Default)]
+pub struct GeneratedConfigDir {
+    /// Generated Evergreen configuration, merged from all configuration files.
+    pub project: EvgProject,
+    /// Tests in each generated sub-suite, keyed by sub-suite name.
+    pub sub_suites: HashMap<String, Vec<String>>,
+}
+
+impl GeneratedConfigDir {
+    /// Load the generated Evergreen configuration and sub-suites from a directory.
+    ///
+    /// # Arguments
+    ///
+    /// * `config_dir` - Directory containing generated configuration.
+    pub fn load(config_dir: &Path) -> Result<Self> {
+        let mut generated = Self::default();
+        let mut paths: Vec<_> = std::fs::read_dir(config_dir)?
+            .filter_map(|e| e.ok().map(|e| e.path()))
+            .collect();
+        paths.sort();
+
+        for path in paths {
+            let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("");
+            let extension = path.extension().and_then(|s| s.to_str()).unwrap_or("");
+            if stem.ends_with(MANIFEST_SUFFIX) || GENERATION_ARTIFACTS.contains(&stem) {
+                continue;
+            }
+
+            let contents = std::fs::read_to_string(&path)?;
+            match extension {
+                "json" => generated.add_project(serde_json::from_str(&contents)?),
+                "yml" | "yaml" => {
+                    let value: serde_yaml::Value = serde_yaml::from_str(&contents)?;
+                    if value.get("buildvariants").is_some() {
+                        generated.add_project(serde_yaml::from_value(value)?);
+                    } else if !stem.ends_with(MISC_SUFFIX) {
+                        let suite_config = ResmokeSuiteConfig::from_str(&contents)?;
+                        generated
+                            .sub_suites
+                            .insert(stem.to_string(), suite_config.get_test_roots());
+                    }
+                }
+                _ => (),
+            }
+        }
+        generated.remove_misc_suites();
+
+        Ok(generated)
+    }
+
+    /// Remove the misc suites of generated display tasks, they are not run by any sub-task.
+    fn remove_misc_suites(&mut self) {
+        for build_variant in &self.project.buildvariants {
+            for display_task in build_variant.display_tasks.iter().flatten() {
+                self.sub_suites
+                    .remove(&misc_suite_name(&display_task.name, &build_variant.name));
+            }
+        }
+    }
+
+    fn add_project(&mut self, project: EvgProject) {
+        self.project.buildvariants.extend(project.buildvariants);
+        self.project.tasks.extend(project.tasks);
+    }
+
+    /// Get the sub-suite each test belongs to, a test may be in one sub-suite per build variant.
+    fn test_locations(&self) -> BTreeMap<&str, BTreeSet<&str>> {
+        let mut locations: BTreeMap<&str, BTreeSet<&str>> = BTreeMap::new();
+        for (sub_suite, tests) in &self.sub_suites {
+            for test in tests {
+                locations
+                    .entry(test.as_str())
+                    .or_default()
+                    .insert(sub_suite.as_str());
+            }
+        }
+        locations
+    }
+
+    /// Get the sub-suite each generated task runs, keyed by task name.
+    fn task_sub_suites(&self) -> HashMap<&str, String> {
+        self.project
+            .tasks
+            .iter()
+            .map(|task| {
+                let suite = task
+                    .commands
+                    .iter()
+                    .find_map(|c| match c {
+                        EvgCommand::Function(func) => match func.vars.as_ref()?.get("suite")? {
+                            ParamValue::String(suite) => Path::new(suite)
+                                .file_stem()
+                                .and_then(|s| s.to_str())
+                                .map(|s| s.to_string()),
+                            _ => None,
+                        },
+                        _ => None,
+                    })
+                    .unwrap_or_else(|| task.name.clone());
+                (task.name.as_str(), suite)
+            })
+            .collect()
+    }
+
+    /// Get the execution tasks of each display task, keyed by build variant and display task.
+    fn display_tasks(&self) -> BTreeMap<(&str, &str), &Vec<String>> {
+        let mut display_tasks = BTreeMap::new();
+        for build_variant in &self.project.buildvariants {
+            for display_task in build_variant.display_tasks.iter().flatten() {
+                display_tasks.insert(
+                    (build_variant.name.as_str(), display_task.name.as_str()),
+                    &display_task.execution_tasks,
+                );
+            }
+        }
+        display_tasks
+    }
+
+    /// Predict the runtime of each display task as the runtime of its longest sub-suite.
+    fn predicted_runtimes(
+        &self,
+        test_runtimes: &HashMap<String, f64>,
+    ) -> BTreeMap<(&str, &str), f64> {
+        let task_sub_suites = self.task_sub_suites();
+        self.display_tasks()
+            .into_iter()
+            .map(|(key, execution_tasks)| {
+                let runtime = execution_tasks
+                    .iter()
+                    .filter_map(|t| task_sub_suites.get(t.as_str()))
+                    .filter_map(|s| self.sub_suites.get(s))
+                    .map(|tests| {
+                        tests
+                            .iter()
+                            .filter_map(|t| test_runtimes.get(t))
+                            .sum::<f64>()
+                    })
+                    .fold(0.0, f64::max);
+                (key, runtime)
+            })
+            .collect()
+    }
+}
+
+/// A test that runs in different sub-suites between two generated configurations.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TestMove {
+    pub test: String,
+    pub before: Vec<String>,
+    pub after: Vec<String>,
+}
+
+/// A display task that was split into a different number of sub-suites.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SubSuiteCountChange {
+    pub build_variant: String,
+    pub task: String,
+    pub before: usize,
+    pub after: usize,
+}
+
+/// A change in the predicted runtime of a display task.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct RuntimeChange {
+    pub build_variant: String,
+    pub task: String,
+    pub before: f64,
+    pub after: f64,
+}
+
+/// Differences between two generated configurations.
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ConfigDiff {
+    pub added_tasks: Vec<String>,
+    pub removed_tasks: Vec<String>,
+    pub moved_tests: Vec<TestMove>,
+    pub sub_suite_count_changes: Vec<SubSuiteCountChange>,
+    pub runtime_changes: Vec<RuntimeChange>,
+}
+
+/// Compare two generated configurations.
+///
+/// # Arguments
+///
+/// * `before` - Original generated configuration.
+/// * `after` - New generated configuration.
+/// * `test_runtimes` - Average runtime of tests, used to predict task runtimes if provided.
+pub fn diff_configs(
+    before: &GeneratedConfigDir,
+    after: &GeneratedConfigDir,
+    test_runtimes: Option<&HashMap<String, f64>>,
+) -> ConfigDiff {
+    let before_tasks: BTreeSet<&str> = before
+        .project
+        .tasks
+        .iter()
+        .map(|t| t.name.as_str())
+        .collect();
+    let after_tasks: BTreeSet<&str> = after
+        .project
+        .tasks
+        .iter()
+        .map(|t| t.name.as_str())
+        .collect();
+
+    let before_locations = before.test_locations();
+    let after_locations = after.test_locations();
+    let all_tests: BTreeSet<&str> = before_locations
+        .keys()
+        .chain(after_locations.keys())
+        .copied()
+        .collect();
+    let empty = BTreeSet::new();
+    let moved_tests = all_tests
+        .into_iter()
+        .filter_map(|test| {
+            let b = before_locations.get(test).unwrap_or(&empty);
+            let a = after_locations.get(test).unwrap_or(&empty);
+            if a != b {
+                Some(TestMove {
+                    test: test.to_string(),
+                    before: b.iter().map(|s| s.to_string()).collect(),
+                    after: a.iter().map(|s| s.to_string()).collect(),
+                })
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    let before_display = before.display_tasks();
+    let after_display = after.display_tasks();
+    let display_keys: BTreeSet<&(&str, &str)> =
+        before_display.keys().chain(after_display.keys()).collect();
+    let sub_suite_count_changes = display_keys
+        .into_iter()
+        .filter_map(|key| {
+            let b = before_display.get(key).map(|t| t.len()).unwrap_or(0);
+            let a = after_display.get(key).map(|t| t.len()).unwrap_or(0);
+            if a != b {
+                Some(SubSuiteCountChange {
+                    build_variant: key.0.to_string(),
+                    task: key.1.to_string(),
+                    before: b,
+                    after: a,
+                })
+            } else {
+                None
+            }
+        })
+        .collect();
+
+    let runtime_changes = test_runtimes
+        .map(|test_runtimes| {
+            let before_runtimes = before.predicted_runtimes(test_runtimes);
+            let after_runtimes = after.predicted_runtimes(test_runtimes);
+            let keys: BTreeSet<&(&str, &str)> = before_runtimes
+                .keys()
+                .chain(after_runtimes.keys())
+                .collect();
+            keys.into_iter()
+                .filter_map(|key| {
+                    let b = before_runtimes.get(key).copied().unwrap_or(0.0);
+                    let a = after_runtimes.get(key).copied().unwrap_or(0.0);
+                    if (a - b).abs() > f64::EPSILON {
+                        Some(RuntimeChange {
+                            build_variant: key.0.to_string(),
+                            task: key.1.to_string(),
+                            before: b,
+                            after: a,
+                        })
+                    } else {
+                        None
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+
+    ConfigDiff {
+        added_tasks: after_tasks
+            .difference(&before_tasks)
+            .map(|t| t.to_string())
+            .collect(),
+        removed_tasks: before_tasks
+            .difference(&after_tasks)
+            .map(|t| t.to_string())
+            .collect(),
+        moved_tests,
+        sub_suite_count_changes,
+        runtime_changes,
+    }
+}
+
+impl Display for ConfigDiff {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Added tasks: {}", self.added_tasks.len())?;
+        for task in &self.added_tasks {
+            writeln!(f, "+ {}", task)?;
+        }
+        writeln!(f, "Removed tasks: {}", self.removed_tasks.len())?;
+        for task in &self.removed_tasks {
+            writeln!(f, "- {}", task)?;
+        }
+        writeln!(f, "Moved tests: {}", self.moved_tests.len())?;
+        for test_move in &self.moved_tests {
+            writeln!(
+                f,
+                "{}: [{}] -> [{}]",
+                test_move.test,
+                test_move.before.join(", "),
+                test_move.after.join(", ")
+            )?;
+        }
+        writeln!(
+            f,
+            "Sub-suite count changes: {}",
+            self.sub_suite_count_changes.len()
+        )?;
+        for change in &self.sub_suite_count_changes {
+            writeln!(
+                f,
+                "{}:{}: {} -> {}",
+                change.build_variant, change.task, change.before, change.after
+            )?;
+        }
+        writeln!(f, "Runtime changes: {}", self.runtime_changes.len())?;
+        for change in &self.runtime_changes {
+            writeln!(
+                f,
+                "{}:{}: {:.1}s -> {:.1}s ({:+.1}s)",
+                change.build_variant,
+                change.task,
+                change.before,
+                change.after,
+                change.after - change.before
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{fingerprint::write_fingerprints, split_tasks::GeneratedSuite};
+    use maplit::hashmap;
+    use shrub_rs::models::{
+        commands::fn_call_with_params,
+        task::EvgTask,
+        variant::{BuildVariant, DisplayTask},
+    };
+
+    fn build_config(sub_suites: &[(&str, &[&str])]) -> GeneratedConfigDir {
+        let tasks: Vec<EvgTask> = sub_suites
+            .iter()
+            .map(|(name, _)| EvgTask {
+                name: name.to_string(),
+                commands: vec![fn_call_with_params(
+                    "run generated tests",
+                    hashmap! {
+                        "suite".to_string() => ParamValue::from(format!("generated_resmoke_config/{}.yml", name).as_str()),
+                    },
+                )],
+                ..Default::default()
+            })
+            .collect();
+        let build_variant = BuildVariant {
+            name: "bv".to_string(),
+            tasks: tasks
+                .iter()
+                .map(|t| t.get_reference(None, Some(false)))
+                .collect(),
+            display_tasks: Some(vec![DisplayTask {
+                name: "task".to_string(),
+                execution_tasks: tasks.iter().map(|t| t.name.clone()).collect(),
+            }]),
+            ..Default::default()
+        };
+
+        GeneratedConfigDir {
+            project: EvgProject {
+                buildvariants: vec![build_variant],
+                tasks,
+                ..Default::default()
+            },
+            sub_suites: sub_suites
+                .iter()
+                .map(|(name, tests)| {
+                    (
+                        name.to_string(),
+                        tests.iter().map(|t| t.to_string()).collect(),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_identical_configs_should_have_no_diff() {
+        let config = build_config(&[
+            ("task_0_bv", &["t0.js", "t1.js"]),
+            ("task_1_bv", &["t2.js"]),
+        ]);
+
+        assert_eq!(diff_configs(&config, &config, None), ConfigDiff::default());
+    }
+
+    #[test]
+    fn test_generation_artifacts_should_not_be_loaded_as_config() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let config_dir = tmp_dir.path();
+        let config = build_config(&[("task_0_bv", &["t0.js"])]);
+        std::fs::write(
+            config_dir.join("evergreen_config.json"),
+            serde_json::to_string(&config.project).unwrap(),
+        )
+        .unwrap();
+        let gen_suite = GeneratedSuite {
+            task_name: "task".to_string(),
+            suite_name: "suite".to_string(),
+            build_variant: "bv".to_string(),
+            sub_suites: vec![],
+            discovery_duration_ms: 0,
+            fingerprint: None,
+            suite_config: None,
+        };
+        write_fingerprints(config_dir, vec![&gen_suite]).unwrap();
+
+        let loaded = GeneratedConfigDir::load(config_dir).unwrap();
+        assert_eq!(loaded.project.buildvariants.len(), 1);
+        assert_eq!(loaded.project.tasks.len(), 1);
+    }
+
+    #[test]
+    fn test_misc_suites_should_not_be_loaded_as_sub_suites() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let config_dir = tmp_dir.path();
+        let config = build_config(&[("task_0_bv", &["t0.js"])]);
+        std::fs::write(
+            config_dir.join("evergreen_config.json"),
+            serde_json::to_string(&config.project).unwrap(),
+        )
+        .unwrap();
+        let suite = "test_kind: js_test\nselector:\n  roots:\n  - t0.js\n";
+        std::fs::write(config_dir.join("task_0_bv.yml"), suite).unwrap();
+        std::fs::write(config_dir.join("task_misc_bv.yml"), suite).unwrap();
+
+        let sub_suites = GeneratedConfigDir::load(config_dir).unwrap().sub_suites;
+        assert_eq!(sub_suites.len(), 1);
+        assert!(sub_suites.contains_key("task_0_bv"));
+    }
+
+    #[test]
+    fn test_diff_should_report_task_test_and_runtime_changes() {
+        let before = build_config(&[
+            ("task_0_bv", &["t0.js", "t1.js"]),
+            ("task_1_bv", &["t2.js"]),
+        ]);
+        let after = build_config(&[
+            ("task_0_bv", &["t0.js"]),
+            ("task_1_bv", &["t1.js"]),
+            ("task_2_bv", &["t2.js"]),
+        ]);
+        let runtimes = hashmap! {
+            "t0.js".to_string() => 10.0,
+            "t1.js".to_string() => 5.0,
+            "t2.js".to_string() => 1.0,
+        };
+
+        let diff = diff_configs(&before, &after, Some(&runtimes));
+
+        assert_eq!(diff.added_tasks, vec!["task_2_bv"]);
+        assert!(diff.removed_tasks.is_empty());
+        assert_eq!(diff.moved_tests.len(), 2);
+        assert_eq!(diff.moved_tests[0].test, "t1.js");
+        assert_eq!(diff.moved_tests[0].before, vec!["task_0_bv"]);
+        assert_eq!(diff.moved_tests[0].after, vec!["task_1_bv"]);
+        assert_eq!(
+            diff.sub_suite_count_changes,
+            vec![SubSuiteCountChange {
+                build_variant: "bv".to_string(),
+                task: "task".to_string(),
+                before: 2,
+                after: 3,
+            }]
+        );
+        assert_eq!(
+            diff.runtime_changes,
+            vec![RuntimeChange {
+                build_variant: "bv".to_string(),
+                task: "task".to_string(),
+                before: 15.0,
+                after: 10.0,
+            }]
+        );
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::path::Path;
+
+use anyhow::Result;
+use lazy_static::lazy_static;
+use regex::{Captures, Regex};
+use shrub_rs::models::variant::BuildVariant;
+
+lazy_static! {
+    static ref EXPANSION_RE: Regex =
+        Regex::new(r"\$\{(?P<id>[a-zA-Z0-9_]+)(\|(?P<default>[^}]*))?}").unwrap();
+}
+
+/// A reference to an expansion that could not be resolved.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ExpansionError {
+    /// The expansion is not defined and no default was given.
+    Undefined { name: String, value: String },
+    /// A `${` that does not start a valid expansion reference.
+    Malformed { value: String },
+}
+
+impl Display for ExpansionError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Undefined { name, value } => write!(
+                f,
+                "expansion '{}' referenced in '{}' is not defined and has no default",
+                name, value
+            ),
+            Self::Malformed { value } => write!(f, "malformed expansion reference in '{}'", value),
+        }
+    }
+}
+
+impl std::error::Error for ExpansionError {}
+
+/// Values available to `${name}` and `${name|default}` references.
+#[derive(Debug, Clone, Default)]
+pub struct Expansions {
+    values: HashMap<String, String>,
+}
+
+impl Expansions {
+    /// Create expansions from a map of values.
+    pub fn new(values: HashMap<String, String>) -> Self {
+        Self { values }
+    }
+
+    /// Read expansions from an Evergreen expansions yaml file.
+    ///
+    /// Non-string values are converted to their yaml representation.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to the expansions file.
+    pub fn from_yaml_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        let raw: HashMap<String, serde_yaml::Value> = serde_yaml::from_str(&contents)?;
+        let values = raw
+            .into_iter()
+            .filter_map(|(k, v)| yaml_to_string(&v).map(|v| (k, v)))
+            .collect();
+        Ok(Self { values })
+    }
+
+    /// Layer the expansions of a build variant on top of these expansions.
+    ///
+    /// Build variant expansions take precedence.
+    ///
+    /// # Arguments
+    ///
+    /// * `build_variant` - Build variant whose expansions should be added.
+    pub fn for_build_variant(&self, build_variant: &BuildVariant) -> Self {
+        let mut values = self.values.clone();
+        if let Some(bv_expansions) = &build_variant.expansions {
+            values.extend(bv_expansions.clone());
+        }
+        Self { values }
+    }
+
+    /// Get the value of an expansion.
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values.get(name).map(|v| v.as_str())
+    }
+
+    /// Resolve all expansion references in the given value.
+    ///
+    /// # Arguments
+    ///
+    /// * `value` - String that may contain `${name}` or `${name|default}` references.
+    pub fn expand(&self, value: &str) -> std::result::Result<String, ExpansionError> {
+        let mut error = None;
+        let expanded = EXPANSION_RE.replace_all(value, |captures: &Captures| {
+            let name = &captures["id"];
+            match (self.get(name), captures.name("default")) {
+                (Some(expansion), _) => expansion.to_string(),
+                (None, Some(default)) => default.as_str().to_string(),
+                (None, None) => {
+                    error.get_or_insert(ExpansionError::Undefined {
+                        name: name.to_string(),
+                        value: value.to_string(),
+                    });
+                    String::new()
+                }
+            }
+        });
+
+        if let Some(error) = error {
+            return Err(error);
+        }
+        if EXPANSION_RE.replace_all(value, "").contains("${") {
+            return Err(ExpansionError::Malformed {
+                value: value.to_string(),
+            });
+        }
+        Ok(expanded.to_string())
+    }
+}
+
+fn yaml_to_string(value: &serde_yaml::Value) -> Option<String> {
+    match value {
+        serde_yaml::Value::String(s) => Some(s.clone()),
+        serde_yaml::Value::Bool(b) => Some(b.to_string()),
+        serde_yaml::Value::Number(n) => Some(n.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maplit::hashmap;
+    use rstest::rstest;
+
+    fn build_expansions() -> Expansions {
+        Expansions::new(hashmap! {
+            "num_files".to_string() => "10".to_string(),
+            "jobs".to_string() => "4".to_string(),
+        })
+    }
+
+    #[rstest]
+    #[case("plain", "plain")]
+    #[case("${num_files}", "10")]
+    #[case("${missing|5}", "5")]
+    #[case("${missing|}", "")]
+    #[case("${num_files|5}", "10")]
+    #[case(
+        "--files=${num_files} --jobs=${jobs|1} --x=${y|z}",
+        "--files=10 --jobs=4 --x=z"
+    )]
+    fn test_expand(#[case] value: &str, #[case] expected: &str) {
+        assert_eq!(build_expansions().expand(value).unwrap(), expected);
+    }
+
+    #[rstest]
+    #[case("${missing}", ExpansionError::Undefined { name: "missing".to_string(), value: "${missing}".to_string() })]
+    #[case("--a=${jobs} ${oops", ExpansionError::Malformed { value: "--a=${jobs} ${oops".to_string() })]
+    fn test_expand_errors(#[case] value: &str, #[case] expected: ExpansionError) {
+        assert_eq!(build_expansions().expand(value), Err(expected));
+    }
+
+    #[test]
+    fn test_build_variant_expansions_should_take_precedence() {
+        let build_variant = BuildVariant {
+            expansions: Some(hashmap! {
+                "jobs".to_string() => "8".to_string(),
+            }),
+            ..Default::default()
+        };
+
+        let expansions = build_expansions().for_build_variant(&build_variant);
+
+        assert_eq!(expansions.expand("${jobs} ${num_files}").unwrap(), "8 10");
+    }
+}
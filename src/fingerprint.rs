@@ -0,0 +1,336 @@
+use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::path::{Path, PathBuf};
+
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    split_tasks::{GeneratedSuite, SplitConfig, SplitStrategy},
+    task_history::TaskRuntimeHistory,
+};
+
+/// Base name of the file listing the fingerprint and split of every generated task.
+pub const FINGERPRINT_NAME: &str = "task_fingerprints";
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0000_0100_0000_01b3;
+
+/// FNV-1a hash, used since it gives the same result on every platform and release.
+struct Fnv1a(u64);
+
+impl Fnv1a {
+    fn new() -> Self {
+        Self(FNV_OFFSET_BASIS)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= u64::from(*byte);
+            self.0 = self.0.wrapping_mul(FNV_PRIME);
+        }
+    }
+
+    fn write_u64(&mut self, value: u64) {
+        self.write(&value.to_le_bytes());
+    }
+
+    /// Write a length prefixed string, so adjacent strings cannot run together.
+    fn write_str(&mut self, value: &str) {
+        self.write_u64(value.len() as u64);
+        self.write(value.as_bytes());
+    }
+}
+
+/// Bucket a test runtime so that small changes in history do not change the fingerprint.
+///
+/// Buckets double in size, so a runtime has to change by roughly 40% to move to another
+/// bucket.
+fn history_bucket(average_runtime: f64) -> u64 {
+    (average_runtime.max(0.0) + 1.0).log2().round() as u64
+}
+
+/// Hash of the inputs a task was split from.
+///
+/// Tasks with the same fingerprint produce the same sub-suites, so their split from a
+/// previous generation can be reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct TaskFingerprint(u64);
+
+impl TaskFingerprint {
+    /// Fingerprint the inputs of a task split.
+    ///
+    /// The resmoke gen params are left out. Only the split and its suite files are reused,
+    /// and neither depends on them: the sub-tasks themselves are generated from the current
+    /// gen params on every run. The only params that change the split are the number of
+    /// sub-suites and the split strategy, which are part of `split_config`.
+    ///
+    /// # Arguments
+    ///
+    /// * `test_list` - Tests discovered for the task's suite.
+    /// * `suite_config` - Resmoke configuration of the suite.
+    /// * `task_history` - Runtime history of the tests in the task.
+    /// * `split_config` - How the task is split into sub-suites.
+    pub fn new(
+        test_list: &[String],
+        suite_config: &str,
+        task_history: &TaskRuntimeHistory,
+        split_config: &SplitConfig,
+    ) -> Self {
+        let mut hasher = Fnv1a::new();
+
+        hasher.write_u64(test_list.len() as u64);
+        for test in test_list {
+            hasher.write_str(test);
+        }
+
+        hasher.write_str(suite_config);
+
+        let mut history: Vec<(&String, u64)> = task_history
+            .test_map
+            .iter()
+            .map(|(name, test)| (name, history_bucket(test.average_runtime)))
+            .collect();
+        history.sort();
+        hasher.write_u64(history.len() as u64);
+        for (name, bucket) in history {
+            hasher.write_str(name);
+            hasher.write_u64(bucket);
+        }
+
+        hasher.write_u64(split_config.n_suites as u64);
+        hasher.write_str(match split_config.strategy {
+            SplitStrategy::Runtime => "runtime",
+            SplitStrategy::TestCount => "test_count",
+        });
+
+        Self(hasher.0)
+    }
+}
+
+impl Display for TaskFingerprint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:016x}", self.0)
+    }
+}
+
+fn fingerprint_path(config_dir: &Path) -> PathBuf {
+    config_dir.join(format!("{}.json", FINGERPRINT_NAME))
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct FingerprintFile {
+    tasks: Vec<GeneratedSuite>,
+}
+
+/// Write the fingerprint and split of every generated task.
+///
+/// # Arguments
+///
+/// * `config_dir` - Directory to write the fingerprint file to.
+/// * `generated_suites` - Splits of every generated task.
+pub fn write_fingerprints<'a>(
+    config_dir: &Path,
+    generated_suites: impl IntoIterator<Item = &'a GeneratedSuite>,
+) -> Result<()> {
+    let mut tasks: Vec<GeneratedSuite> = generated_suites.into_iter().cloned().collect();
+    tasks.sort_by(|a, b| (&a.build_variant, &a.task_name).cmp(&(&b.build_variant, &b.task_name)));
+    std::fs::write(
+        fingerprint_path(config_dir),
+        serde_json::to_string_pretty(&FingerprintFile { tasks })?,
+    )?;
+    Ok(())
+}
+
+/// Splits written by a previous generation.
+#[derive(Debug, Default)]
+pub struct PreviousGeneration {
+    config_dir: PathBuf,
+    suites: HashMap<(String, String), GeneratedSuite>,
+}
+
+impl PreviousGeneration {
+    /// Load the splits of a previous generation from its output directory.
+    ///
+    /// A directory without a fingerprint file has no reusable splits.
+    ///
+    /// # Arguments
+    ///
+    /// * `config_dir` - Output directory of the previous generation.
+    pub fn load(config_dir: &Path) -> Result<Self> {
+        let path = fingerprint_path(config_dir);
+        let fingerprints: FingerprintFile = if path.exists() {
+            serde_json::from_str(&std::fs::read_to_string(path)?)?
+        } else {
+            FingerprintFile::default()
+        };
+
+        Ok(Self {
+            config_dir: config_dir.to_path_buf(),
+            suites: fingerprints
+                .tasks
+                .into_iter()
+                .map(|s| ((s.task_name.clone(), s.build_variant.clone()), s))
+                .collect(),
+        })
+    }
+
+    /// Get the previous split of a task if its fingerprint has not changed.
+    ///
+    /// # Arguments
+    ///
+    /// * `task_name` - Name of the task.
+    /// * `build_variant` - Build variant the task is split for.
+    /// * `fingerprint` - Fingerprint of the current inputs of the split.
+    pub fn unchanged_suite(
+        &self,
+        task_name: &str,
+        build_variant: &str,
+        fingerprint: TaskFingerprint,
+    ) -> Option<&GeneratedSuite> {
+        self.suites
+            .get(&(task_name.to_string(), build_variant.to_string()))
+            .filter(|s| s.fingerprint == Some(fingerprint))
+    }
+
+    /// Check if a split is the same as the one in the previous generation.
+    pub fn is_unchanged(&self, gen_suite: &GeneratedSuite) -> bool {
+        gen_suite
+            .fingerprint
+            .and_then(|fingerprint| {
+                self.unchanged_suite(&gen_suite.task_name, &gen_suite.build_variant, fingerprint)
+            })
+            .is_some()
+    }
+
+    /// Copy the suite files of an unchanged split from the previous generation.
+    ///
+    /// Nothing is copied if the previous generation was written to the same directory, since
+    /// its suite files are already in place.
+    ///
+    /// # Arguments
+    ///
+    /// * `gen_suite` - Split to copy the suite files of.
+    /// * `config_dir` - Directory to copy the suite files to.
+    pub fn copy_suite_files(&self, gen_suite: &GeneratedSuite, config_dir: &Path) -> Result<()> {
+        if self.config_dir.canonicalize()? == config_dir.canonicalize()? {
+            return Ok(());
+        }
+        let file_names = gen_suite
+            .sub_suites
+            .iter()
+            .map(|s| format!("{}.yml", s.name))
+            .chain(std::iter::once(format!(
+                "{}.yml",
+                gen_suite.misc_suite_name()
+            )));
+        for file_name in file_names {
+            std::fs::copy(
+                self.config_dir.join(&file_name),
+                config_dir.join(&file_name),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::split_tasks::SubSuite;
+    use crate::task_history::TestRuntimeHistory;
+
+    fn build_history(runtimes: &[(&str, f64)]) -> TaskRuntimeHistory {
+        TaskRuntimeHistory {
+            suite_name: "suite".to_string(),
+            task_name: "task".to_string(),
+            test_map: runtimes
+                .iter()
+                .map(|(name, runtime)| {
+                    (
+                        name.to_string(),
+                        TestRuntimeHistory {
+                            test_name: name.to_string(),
+                            average_runtime: *runtime,
+                            hooks: vec![],
+                        },
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    fn fingerprint(tests: &[&str], history: &[(&str, f64)], n_suites: usize) -> TaskFingerprint {
+        let test_list: Vec<String> = tests.iter().map(|t| t.to_string()).collect();
+        let split_config = SplitConfig {
+            n_suites,
+            strategy: SplitStrategy::Runtime,
+        };
+        TaskFingerprint::new(
+            &test_list,
+            "test_kind: js_test",
+            &build_history(history),
+            &split_config,
+        )
+    }
+
+    #[test]
+    fn test_fingerprint_should_ignore_small_history_changes() {
+        let original = fingerprint(&["t0.js", "t1.js"], &[("t0", 100.0), ("t1", 50.0)], 2);
+
+        assert_eq!(
+            original,
+            fingerprint(&["t0.js", "t1.js"], &[("t0", 105.0), ("t1", 48.0)], 2)
+        );
+        assert_ne!(
+            original,
+            fingerprint(&["t0.js", "t1.js"], &[("t0", 300.0), ("t1", 50.0)], 2)
+        );
+    }
+
+    #[test]
+    fn test_fingerprint_should_change_with_tests_and_params() {
+        let original = fingerprint(&["t0.js", "t1.js"], &[], 2);
+
+        assert_ne!(original, fingerprint(&["t0.js", "t2.js"], &[], 2));
+        assert_ne!(original, fingerprint(&["t0.js t1.js"], &[], 2));
+        assert_ne!(original, fingerprint(&["t0.js", "t1.js"], &[], 3));
+    }
+
+    #[test]
+    fn test_unchanged_suites_should_be_reused() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let config_dir = tmp_dir.path();
+        let fingerprint = fingerprint(&["t0.js"], &[], 1);
+        let gen_suite = GeneratedSuite {
+            task_name: "task".to_string(),
+            suite_name: "suite".to_string(),
+            build_variant: "bv".to_string(),
+            sub_suites: vec![SubSuite {
+                name: "task_0_bv".to_string(),
+                test_list: vec!["t0.js".to_string()],
+            }],
+            discovery_duration_ms: 0,
+            fingerprint: Some(fingerprint),
+            suite_config: None,
+        };
+
+        write_fingerprints(config_dir, vec![&gen_suite]).unwrap();
+        let suite_file = config_dir.join("task_0_bv.yml");
+        std::fs::write(&suite_file, "test_kind: js_test").unwrap();
+        let previous = PreviousGeneration::load(config_dir).unwrap();
+        previous.copy_suite_files(&gen_suite, config_dir).unwrap();
+        let suite_contents = std::fs::read_to_string(&suite_file).unwrap();
+
+        assert!(previous.is_unchanged(&gen_suite));
+        assert_eq!(suite_contents, "test_kind: js_test");
+        assert!(previous
+            .unchanged_suite("task", "other_bv", fingerprint)
+            .is_none());
+        let other_fingerprint = TaskFingerprint(0);
+        assert!(previous
+            .unchanged_suite("task", "bv", other_fingerprint)
+            .is_none());
+    }
+}
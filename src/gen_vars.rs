@@ -0,0 +1,359 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use anyhow::{Context, Result};
+use serde::{
+    de::{self, DeserializeOwned, Visitor},
+    forward_to_deserialize_any, Deserialize, Deserializer,
+};
+use shrub_rs::models::{params::ParamValue, task::EvgTask};
+
+use crate::{expansions::Expansions, generator_functions::GeneratorFunctions};
+
+/// Vars of the `generate resmoke tasks` function for a resmoke task.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct ResmokeGenVars {
+    /// Resmoke suite to run, defaults to the task name.
+    pub suite: Option<String>,
+    /// Arguments to pass to resmoke.
+    #[serde(default)]
+    pub resmoke_args: String,
+    /// Should sub-tasks run on the large distro.
+    #[serde(default, deserialize_with = "string_or_native")]
+    pub use_large_distro: bool,
+    /// Maximum number of jobs resmoke should execute in parallel.
+    #[serde(default, deserialize_with = "optional_string_or_native")]
+    pub resmoke_jobs_max: Option<u64>,
+}
+
+impl ResmokeGenVars {
+    /// Read the generate vars of a resmoke task.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - Task definition to read vars from.
+    /// * `generator_functions` - Functions that mark a task as generated.
+    /// * `expansions` - Expansions to resolve references against.
+    pub fn from_task(
+        task: &EvgTask,
+        generator_functions: &GeneratorFunctions,
+        expansions: &Expansions,
+    ) -> Result<Self> {
+        load_gen_vars(task, generator_functions, expansions)
+    }
+}
+
+fn default_npm_command() -> String {
+    "jstestfuzz".to_string()
+}
+
+/// Vars of the `generate resmoke tasks` function for a fuzzer task.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct FuzzerGenVars {
+    /// Resmoke suite to run, defaults to the task name.
+    pub suite: Option<String>,
+    /// Number of javascript files fuzzer should generate.
+    #[serde(deserialize_with = "string_or_native")]
+    pub num_files: u64,
+    /// Number of sub-tasks fuzzer should generate.
+    #[serde(deserialize_with = "string_or_native")]
+    pub num_tasks: u64,
+    /// Arguments to pass to resmoke.
+    pub resmoke_args: String,
+    /// NPM command to perform fuzzer execution.
+    #[serde(default = "default_npm_command")]
+    pub npm_command: String,
+    /// Arguments to pass to fuzzer invocation.
+    pub jstestfuzz_vars: Option<String>,
+    /// Should generated tests continue running after hitting error.
+    #[serde(deserialize_with = "string_or_native")]
+    pub continue_on_failure: bool,
+    /// Maximum number of jobs resmoke should execute in parallel.
+    #[serde(deserialize_with = "string_or_native")]
+    pub resmoke_jobs_max: u64,
+    /// Should tests be executed out of order.
+    #[serde(deserialize_with = "string_or_native")]
+    pub should_shuffle: bool,
+    /// Timeout before test execution is considered hung.
+    #[serde(deserialize_with = "string_or_native")]
+    pub timeout_secs: u64,
+    /// Should sub-tasks run on the large distro.
+    #[serde(default, deserialize_with = "optional_string_or_native")]
+    pub use_large_distro: Option<bool>,
+}
+
+impl FuzzerGenVars {
+    /// Read the generate vars of a fuzzer task.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - Task definition to read vars from.
+    /// * `generator_functions` - Functions that mark a task as generated.
+    /// * `expansions` - Expansions to resolve references against.
+    pub fn from_task(
+        task: &EvgTask,
+        generator_functions: &GeneratorFunctions,
+        expansions: &Expansions,
+    ) -> Result<Self> {
+        load_gen_vars(task, generator_functions, expansions)
+    }
+}
+
+/// Deserialize the vars of a task's generator function.
+///
+/// Expansion references are resolved in the string values of the deserialized vars. Other vars
+/// are passed to the function unchanged, so they may reference expansions that are only defined
+/// when the task runs.
+fn load_gen_vars<T: DeserializeOwned>(
+    task: &EvgTask,
+    generator_functions: &GeneratorFunctions,
+    expansions: &Expansions,
+) -> Result<T> {
+    let vars = generator_functions
+        .generate_func(task)
+        .and_then(|f| f.vars.clone())
+        .unwrap_or_default();
+
+    let names = field_names::<T>();
+    let mut values = serde_json::Map::new();
+    for (name, value) in vars {
+        if !names.contains(&name.as_str()) {
+            continue;
+        }
+        let value = match value {
+            ParamValue::String(value) => {
+                serde_json::Value::String(expansions.expand(&value).with_context(|| {
+                    format!("Could not expand var '{}' of task '{}'", name, task.name)
+                })?)
+            }
+            value => serde_json::to_value(value)?,
+        };
+        values.insert(name, value);
+    }
+
+    serde_json::from_value(serde_json::Value::Object(values))
+        .with_context(|| format!("Invalid generate vars for task '{}'", task.name))
+}
+
+/// Names of the fields a struct is deserialized from.
+///
+/// The derived `Deserialize` implementation passes them to the deserializer, so they are
+/// captured by one that records them instead of reading any data.
+fn field_names<T: DeserializeOwned>() -> &'static [&'static str] {
+    let mut fields = &[][..];
+    let _ = T::deserialize(FieldNamesDeserializer {
+        fields: &mut fields,
+    });
+    fields
+}
+
+/// Deserializer recording the field names of the struct being deserialized.
+struct FieldNamesDeserializer<'a> {
+    fields: &'a mut &'static [&'static str],
+}
+
+impl<'de, 'a> Deserializer<'de> for FieldNamesDeserializer<'a> {
+    type Error = de::value::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, _visitor: V) -> Result<V::Value, Self::Error> {
+        Err(de::Error::custom("only structs have field names"))
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _name: &'static str,
+        fields: &'static [&'static str],
+        _visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        *self.fields = fields;
+        Err(de::Error::custom("field names recorded"))
+    }
+
+    forward_to_deserialize_any! {
+        bool i8 i16 i32 i64 i128 u8 u16 u32 u64 u128 f32 f64 char str string bytes byte_buf
+        option unit unit_struct newtype_struct seq tuple tuple_struct map enum identifier
+        ignored_any
+    }
+}
+
+/// A value that may be given natively or as a string.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StringOrNative<T> {
+    Native(T),
+    String(String),
+}
+
+impl<T> StringOrNative<T>
+where
+    T: FromStr,
+    T::Err: Display,
+{
+    fn into_value<E: serde::de::Error>(self) -> Result<T, E> {
+        match self {
+            Self::Native(value) => Ok(value),
+            Self::String(value) => value
+                .parse()
+                .map_err(|e| E::custom(format!("invalid value '{}': {}", value, e))),
+        }
+    }
+}
+
+fn string_or_native<'de, D, T>(deserializer: D) -> Result<T, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + FromStr,
+    T::Err: Display,
+{
+    StringOrNative::deserialize(deserializer)?.into_value()
+}
+
+fn optional_string_or_native<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de> + FromStr,
+    T::Err: Display,
+{
+    Option::<StringOrNative<T>>::deserialize(deserializer)?
+        .map(|v| v.into_value())
+        .transpose()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maplit::hashmap;
+    use shrub_rs::models::commands::fn_call_with_params;
+    use std::collections::HashMap;
+
+    fn build_task(vars: HashMap<String, ParamValue>) -> EvgTask {
+        EvgTask {
+            name: "my_fuzzer_gen".to_string(),
+            commands: vec![fn_call_with_params("generate resmoke tasks", vars)],
+            ..Default::default()
+        }
+    }
+
+    fn fuzzer_vars() -> HashMap<String, ParamValue> {
+        hashmap! {
+            "num_files".to_string() => ParamValue::from("${num_files|5}"),
+            "num_tasks".to_string() => ParamValue::from(10),
+            "resmoke_args".to_string() => ParamValue::from("--storageEngine=wiredTiger"),
+            "continue_on_failure".to_string() => ParamValue::from("false"),
+            "resmoke_jobs_max".to_string() => ParamValue::from(1),
+            "should_shuffle".to_string() => ParamValue::from(false),
+            "timeout_secs".to_string() => ParamValue::from("1800"),
+            "is_jstestfuzz".to_string() => ParamValue::from(true),
+        }
+    }
+
+    #[test]
+    fn test_fuzzer_vars_should_accept_strings_and_native_values() {
+        let task = build_task(fuzzer_vars());
+
+        let vars = FuzzerGenVars::from_task(
+            &task,
+            &GeneratorFunctions::default(),
+            &Expansions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(vars.num_files, 5);
+        assert_eq!(vars.num_tasks, 10);
+        assert!(!vars.continue_on_failure);
+        assert!(!vars.should_shuffle);
+        assert_eq!(vars.timeout_secs, 1800);
+        assert_eq!(vars.npm_command, "jstestfuzz");
+        assert_eq!(vars.use_large_distro, None);
+    }
+
+    #[test]
+    fn test_missing_required_var_should_name_task_and_var() {
+        let mut vars = fuzzer_vars();
+        vars.remove("num_tasks");
+        let task = build_task(vars);
+
+        let error = FuzzerGenVars::from_task(
+            &task,
+            &GeneratorFunctions::default(),
+            &Expansions::default(),
+        )
+        .unwrap_err();
+        let message = format!("{:#}", error);
+
+        assert!(message.contains("my_fuzzer_gen"));
+        assert!(message.contains("num_tasks"));
+    }
+
+    #[test]
+    fn test_invalid_value_should_be_an_error() {
+        let mut vars = fuzzer_vars();
+        vars.insert("num_tasks".to_string(), ParamValue::from("many"));
+        let task = build_task(vars);
+
+        let error = FuzzerGenVars::from_task(
+            &task,
+            &GeneratorFunctions::default(),
+            &Expansions::default(),
+        )
+        .unwrap_err();
+
+        assert!(format!("{:#}", error).contains("invalid value 'many'"));
+    }
+
+    #[test]
+    fn test_only_deserialized_vars_should_be_expanded() {
+        let mut vars = fuzzer_vars();
+        vars.insert(
+            "test_flags".to_string(),
+            ParamValue::from("${runtime_flags}"),
+        );
+        let task = build_task(vars);
+
+        let vars = FuzzerGenVars::from_task(
+            &task,
+            &GeneratorFunctions::default(),
+            &Expansions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(vars.num_files, 5);
+    }
+
+    #[test]
+    fn test_field_names_should_match_struct_fields() {
+        assert_eq!(
+            field_names::<ResmokeGenVars>(),
+            &[
+                "suite",
+                "resmoke_args",
+                "use_large_distro",
+                "resmoke_jobs_max"
+            ]
+        );
+        assert_eq!(field_names::<FuzzerGenVars>().len(), 11);
+        assert!(field_names::<FuzzerGenVars>().contains(&"jstestfuzz_vars"));
+    }
+
+    #[test]
+    fn test_resmoke_vars_should_use_defaults() {
+        let task = build_task(hashmap! {
+            "use_large_distro".to_string() => ParamValue::from("true"),
+        });
+
+        let vars = ResmokeGenVars::from_task(
+            &task,
+            &GeneratorFunctions::default(),
+            &Expansions::default(),
+        )
+        .unwrap();
+
+        assert_eq!(
+            vars,
+            ResmokeGenVars {
+                use_large_distro: true,
+                ..Default::default()
+            }
+        );
+    }
+}
@@ -0,0 +1,998 @@
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+use anyhow::{bail, Context, Result};
+use serde::Deserialize;
+use shrub_rs::models::{
+    project::EvgProject,
+    task::{EvgTask, TaskRef},
+    task_group::EvgTaskGroup,
+    variant::{BuildVariant, DisplayTask},
+};
+use tracing::{event, Level};
+
+use crate::{
+    activation::Requester,
+    expansions::Expansions,
+    fingerprint::PreviousGeneration,
+    generator_config::GeneratorConfig,
+    output::sort_config,
+    report::GenerationReport,
+    resmoke::{ResmokeProxy, TestDiscovery},
+    split_tasks::{GeneratedSuite, SplitConfig, SplitStrategy, TaskSplitter, TaskSplitting},
+    task_history::TaskHistoryService,
+    task_properties::{compile_variant, ExtraTaskProperties},
+    task_types::{
+        fuzzer_tasks::{FuzzerTaskGenerator, GenFuzzerService},
+        resmoke_tasks::ResmokeTaskGenerator,
+        task_type_generator::{GeneratedTask, TaskGenContext, TaskTypeGenerator, TaskTypeRegistry},
+    },
+    taskname::remove_gen_suffix_ref,
+    validate::validate_generated_config,
+    variant_filter::VariantFilter,
+    write_config::SuiteFileSink,
+};
+
+/// Directory generated configuration is written to.
+pub const CONFIG_DIR: &str = "generated_resmoke_config";
+
+/// Data extracted from Evergreen expansions.
+#[derive(Debug, Deserialize, Clone)]
+pub struct EvgExpansions {
+    /// ID of build being run under.
+    pub build_id: Option<String>,
+    /// Build variant being generated.
+    pub build_variant: Option<String>,
+    /// Whether a patch build is being generated.
+    pub is_patch: Option<String>,
+    /// Kind of request that created the version.
+    pub requester: Option<String>,
+    /// Evergreen project being generated on.
+    pub project: String,
+    /// Max number of tests to add to each suite.
+    pub max_tests_per_suite: Option<usize>,
+    /// Maximum number of sub suites to generate in patch builds.
+    pub max_sub_suite: Option<usize>,
+    /// Maximum number of suites to generate on mainline builds
+    pub mainline_max_sub_suites: Option<usize>,
+    /// Repeat parameters to pass to resmoke.
+    pub resmoke_repeat_suites: Option<usize>,
+    /// Git revision being run against.
+    pub revision: String,
+    /// Name of task doing the generation.
+    pub task_name: Option<String>,
+    /// Target runtime for generated tasks.
+    pub target_resmoke_time: Option<String>,
+    /// ID of task doing the generation.
+    pub task_id: Option<String>,
+    /// ID of Evergreen version running.
+    pub version_id: Option<String>,
+}
+
+impl EvgExpansions {
+    /// Read the expansions from a yaml file.
+    pub fn from_yaml_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(serde_yaml::from_str(&contents)?)
+    }
+
+    /// Determine the max sub suites to split into.
+    pub fn get_max_sub_suites(&self) -> usize {
+        if let Some(is_patch) = &self.is_patch {
+            if is_patch == "true" {
+                return self.max_sub_suite.unwrap_or(5);
+            }
+        }
+        self.mainline_max_sub_suites.unwrap_or(1)
+    }
+
+    /// Determine the requester of the version, falling back to `is_patch` if not given.
+    pub fn requester(&self) -> Result<Requester> {
+        match &self.requester {
+            Some(requester) => requester.parse(),
+            None if self.is_patch.as_deref() == Some("true") => Ok(Requester::Patch),
+            None => Ok(Requester::Mainline),
+        }
+    }
+
+    /// Location generated configuration is uploaded to.
+    ///
+    /// # Arguments
+    ///
+    /// * `scope` - What is being generated.
+    pub fn config_location(&self, scope: &GenerationScope) -> Result<String> {
+        match scope {
+            GenerationScope::Version => Ok(format!(
+                "{}/generate_tasks/generated-config-{}.tgz",
+                self.revision,
+                required_expansion(&self.version_id, "version_id")?
+            )),
+            GenerationScope::BuildVariant(build_variant) => {
+                let task_name = required_expansion(&self.task_name, "task_name")?;
+                Ok(format!(
+                    "{}/{}/generate_tasks/{}_gen-{}.tgz",
+                    build_variant,
+                    self.revision,
+                    remove_gen_suffix_ref(task_name),
+                    required_expansion(&self.build_id, "build_id")?
+                ))
+            }
+        }
+    }
+}
+
+fn required_expansion<'a>(value: &'a Option<String>, name: &str) -> Result<&'a str> {
+    value
+        .as_deref()
+        .with_context(|| format!("Expansion '{}' is required", name))
+}
+
+/// What a generator generates.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GenerationScope {
+    /// Every build variant in the project.
+    Version,
+    /// A single build variant.
+    BuildVariant(String),
+}
+
+/// Configuration generated for a single build variant.
+#[derive(Debug, Clone, Default)]
+struct GeneratedConfig {
+    pub gen_task_def: Vec<EvgTask>,
+    pub gen_task_specs: Vec<TaskRef>,
+    pub display_tasks: Vec<DisplayTask>,
+    pub task_groups: Vec<EvgTaskGroup>,
+    pub generated_suites: Vec<GeneratedSuite>,
+    pub task_properties: HashMap<String, ExtraTaskProperties>,
+}
+
+impl GeneratedConfig {
+    /// Add the configuration generated for a task.
+    ///
+    /// # Arguments
+    ///
+    /// * `generated_task` - Configuration generated for the task.
+    /// * `extra_properties` - Extra properties of the `_gen` task its sub-tasks inherit.
+    fn add_task(
+        &mut self,
+        generated_task: GeneratedTask,
+        extra_properties: Option<&ExtraTaskProperties>,
+    ) {
+        if let Some(extra_properties) = extra_properties {
+            self.task_properties.extend(
+                generated_task
+                    .tasks
+                    .iter()
+                    .map(|t| (t.name.clone(), extra_properties.clone())),
+            );
+        }
+        self.gen_task_def.extend(generated_task.tasks);
+        self.gen_task_specs.extend(generated_task.task_refs);
+        self.display_tasks.extend(generated_task.display_tasks);
+        self.task_groups.extend(generated_task.task_groups);
+        self.generated_suites.extend(generated_task.generated_suite);
+    }
+}
+
+/// Configuration produced by a generator.
+#[derive(Debug)]
+pub struct GenerationOutput {
+    /// Generated Evergreen configuration.
+    pub project: EvgProject,
+    /// Report of the generated tasks.
+    pub report: GenerationReport,
+    /// Splits of the generated resmoke tasks.
+    pub generated_suites: Vec<GeneratedSuite>,
+    /// Extra properties of generated tasks, by task name, to add when writing the project.
+    pub task_properties: HashMap<String, ExtraTaskProperties>,
+}
+
+/// Builder for a [`Generator`].
+#[derive(Default)]
+pub struct GeneratorBuilder {
+    evg_project: Option<EvgProject>,
+    variant_tags: HashMap<String, Vec<String>>,
+    extra_task_properties: HashMap<String, ExtraTaskProperties>,
+    scope: Option<GenerationScope>,
+    config_location: Option<String>,
+    expansions: Expansions,
+    generator_config: GeneratorConfig,
+    requester: Option<Requester>,
+    variant_filter: VariantFilter,
+    split_config: Option<SplitConfig>,
+    previous_generation: Option<PreviousGeneration>,
+    task_history_service: Option<Arc<dyn TaskHistoryService>>,
+    test_discovery: Option<Arc<dyn TestDiscovery>>,
+    task_splitter: Option<Arc<dyn TaskSplitting>>,
+    gen_fuzzer_service: Option<Arc<dyn GenFuzzerService>>,
+    suite_sink: Option<Arc<dyn SuiteFileSink>>,
+    task_type_generators: Vec<Arc<dyn TaskTypeGenerator>>,
+}
+
+impl GeneratorBuilder {
+    /// Evaluated project configuration to generate tasks from.
+    pub fn evg_project(mut self, evg_project: EvgProject) -> Self {
+        self.evg_project = Some(evg_project);
+        self
+    }
+
+    /// Tags of each build variant in the project, used to filter build variants.
+    pub fn variant_tags(mut self, variant_tags: HashMap<String, Vec<String>>) -> Self {
+        self.variant_tags = variant_tags;
+        self
+    }
+
+    /// Properties of each task missing from the project model, inherited by sub-tasks.
+    pub fn extra_task_properties(
+        mut self,
+        extra_task_properties: HashMap<String, ExtraTaskProperties>,
+    ) -> Self {
+        self.extra_task_properties = extra_task_properties;
+        self
+    }
+
+    /// What to generate.
+    pub fn scope(mut self, scope: GenerationScope) -> Self {
+        self.scope = Some(scope);
+        self
+    }
+
+    /// Location generated configuration is uploaded to.
+    pub fn config_location(mut self, config_location: &str) -> Self {
+        self.config_location = Some(config_location.to_string());
+        self
+    }
+
+    /// Expansions available to the generated build variants.
+    pub fn expansions(mut self, expansions: Expansions) -> Self {
+        self.expansions = expansions;
+        self
+    }
+
+    /// Configuration of how tasks are generated.
+    pub fn generator_config(mut self, generator_config: GeneratorConfig) -> Self {
+        self.generator_config = generator_config;
+        self
+    }
+
+    /// Requester of the version being generated, mainline if not given.
+    pub fn requester(mut self, requester: Requester) -> Self {
+        self.requester = Some(requester);
+        self
+    }
+
+    /// Filter selecting the build variants and tasks to generate.
+    pub fn variant_filter(mut self, variant_filter: VariantFilter) -> Self {
+        self.variant_filter = variant_filter;
+        self
+    }
+
+    /// Default way to split resmoke tasks, used if no task splitter is given.
+    pub fn split_config(mut self, split_config: SplitConfig) -> Self {
+        self.split_config = Some(split_config);
+        self
+    }
+
+    /// Previous generation to reuse the splits of unchanged tasks from.
+    pub fn previous_generation(mut self, previous_generation: PreviousGeneration) -> Self {
+        self.previous_generation = Some(previous_generation);
+        self
+    }
+
+    /// Service to look up the runtime history of tests.
+    pub fn task_history_service(
+        mut self,
+        task_history_service: Arc<dyn TaskHistoryService>,
+    ) -> Self {
+        self.task_history_service = Some(task_history_service);
+        self
+    }
+
+    /// Service to discover tests in a suite, resmoke if not given.
+    pub fn test_discovery(mut self, test_discovery: Arc<dyn TestDiscovery>) -> Self {
+        self.test_discovery = Some(test_discovery);
+        self
+    }
+
+    /// Service to split resmoke tasks into sub-suites.
+    ///
+    /// If not given, tasks are split with the split config and test discovery.
+    pub fn task_splitter(mut self, task_splitter: Arc<dyn TaskSplitting>) -> Self {
+        self.task_splitter = Some(task_splitter);
+        self
+    }
+
+    /// Service to generate fuzzer tasks.
+    pub fn gen_fuzzer_service(mut self, gen_fuzzer_service: Arc<dyn GenFuzzerService>) -> Self {
+        self.gen_fuzzer_service = Some(gen_fuzzer_service);
+        self
+    }
+
+    /// Destination for the suite files of generated tasks.
+    pub fn suite_sink(mut self, suite_sink: Arc<dyn SuiteFileSink>) -> Self {
+        self.suite_sink = Some(suite_sink);
+        self
+    }
+
+    /// Add a generator for another type of task.
+    ///
+    /// Generators are checked in the order they are added, before the resmoke and fuzzer
+    /// generators.
+    pub fn task_type_generator(mut self, task_type_generator: Arc<dyn TaskTypeGenerator>) -> Self {
+        self.task_type_generators.push(task_type_generator);
+        self
+    }
+
+    /// Create the generator.
+    ///
+    /// It is an error to not give a project, scope, config location, task history
+    /// service, fuzzer service or suite sink.
+    pub fn build(self) -> Result<Generator> {
+        let generator_config = Arc::new(self.generator_config);
+        let previous_generation = self.previous_generation.map(Arc::new);
+        let task_splitter = match self.task_splitter {
+            Some(task_splitter) => task_splitter,
+            None => Arc::new(TaskSplitter {
+                test_discovery: self
+                    .test_discovery
+                    .unwrap_or_else(|| Arc::new(ResmokeProxy {})),
+                split_config: self.split_config.unwrap_or(SplitConfig {
+                    n_suites: 1,
+                    strategy: SplitStrategy::Runtime,
+                }),
+                generator_config: generator_config.clone(),
+                previous_generation: previous_generation.clone(),
+            }),
+        };
+
+        Ok(Generator {
+            evg_project: Arc::new(self.evg_project.context("A project is required")?),
+            variant_tags: Arc::new(self.variant_tags),
+            extra_task_properties: Arc::new(self.extra_task_properties),
+            scope: self.scope.context("A generation scope is required")?,
+            config_location: self
+                .config_location
+                .context("A config location is required")?,
+            expansions: Arc::new(self.expansions),
+            generator_config,
+            requester: self.requester.unwrap_or(Requester::Mainline),
+            variant_filter: Arc::new(self.variant_filter),
+            previous_generation,
+            task_history_service: self
+                .task_history_service
+                .context("A task history service is required")?,
+            task_splitter,
+            gen_fuzzer_service: self
+                .gen_fuzzer_service
+                .context("A fuzzer service is required")?,
+            suite_sink: self.suite_sink.context("A suite sink is required")?,
+            task_type_generators: self.task_type_generators,
+        })
+    }
+}
+
+/// Generates the sub-tasks of the `_gen` tasks in a project.
+///
+/// ```ignore
+/// let generator = Generator::builder()
+///     .evg_project(evg_project)
+///     .scope(GenerationScope::BuildVariant("linux".to_string()))
+///     .config_location(&config_location)
+///     .task_history_service(task_history_service)
+///     .gen_fuzzer_service(gen_fuzzer_service)
+///     .suite_sink(Arc::new(WriteConfigActorHandle::new(CONFIG_DIR)))
+///     .build()?;
+/// let output = generator.generate().await?;
+/// ```
+#[derive(Clone)]
+pub struct Generator {
+    evg_project: Arc<EvgProject>,
+    variant_tags: Arc<HashMap<String, Vec<String>>>,
+    extra_task_properties: Arc<HashMap<String, ExtraTaskProperties>>,
+    scope: GenerationScope,
+    config_location: String,
+    expansions: Arc<Expansions>,
+    generator_config: Arc<GeneratorConfig>,
+    requester: Requester,
+    variant_filter: Arc<VariantFilter>,
+    previous_generation: Option<Arc<PreviousGeneration>>,
+    task_history_service: Arc<dyn TaskHistoryService>,
+    task_splitter: Arc<dyn TaskSplitting>,
+    gen_fuzzer_service: Arc<dyn GenFuzzerService>,
+    suite_sink: Arc<dyn SuiteFileSink>,
+    task_type_generators: Vec<Arc<dyn TaskTypeGenerator>>,
+}
+
+impl Generator {
+    /// Start building a generator.
+    pub fn builder() -> GeneratorBuilder {
+        GeneratorBuilder::default()
+    }
+
+    /// Generate the sub-tasks of every `_gen` task in scope.
+    ///
+    /// Suite files are written to the suite sink, and it is flushed before returning.
+    pub async fn generate(&self) -> Result<GenerationOutput> {
+        let build_variants = self.build_variants()?;
+        let build_variant_map = self.evg_project.build_variant_map();
+        for bv_name in &build_variants {
+            compile_variant(
+                self.build_variant(bv_name)?,
+                &self.expansions,
+                &build_variant_map,
+            )?;
+        }
+
+        let generation_report = Arc::new(Mutex::new(GenerationReport::default()));
+        let registry = Arc::new(self.task_type_registry());
+
+        let mut handles = vec![];
+        for bv_name in build_variants {
+            let generator = self.clone();
+            let registry = registry.clone();
+            let generation_report = generation_report.clone();
+            handles.push(tokio::spawn(async move {
+                generator
+                    .generate_build_variant(&bv_name, &registry, generation_report)
+                    .await
+            }));
+        }
+
+        let mut gen_build_variants = vec![];
+        let mut task_definitions = vec![];
+        let mut task_groups = vec![];
+        let mut generated_suites = vec![];
+        let mut task_properties = HashMap::new();
+        for handle in handles {
+            let (gen_build_variant, gen_config) = handle.await??;
+            gen_build_variants.push(gen_build_variant);
+            task_definitions.extend(gen_config.gen_task_def);
+            task_groups.extend(gen_config.task_groups);
+            generated_suites.extend(gen_config.generated_suites);
+            task_properties.extend(gen_config.task_properties);
+        }
+
+        let mut project = EvgProject {
+            buildvariants: gen_build_variants,
+            tasks: task_definitions,
+            task_groups: Some(task_groups).filter(|tg| !tg.is_empty()),
+            ..Default::default()
+        };
+        sort_config(&mut project);
+
+        self.suite_sink.flush().await;
+
+        let validation_errors =
+            validate_generated_config(&project, |suite| self.suite_sink.has_suite_file(suite));
+        if !validation_errors.is_empty() {
+            for error in &validation_errors {
+                event!(Level::ERROR, "Invalid generated config: {}", error);
+            }
+            bail!(
+                "Generated config has {} validation errors",
+                validation_errors.len()
+            );
+        }
+
+        let report = std::mem::take(&mut *generation_report.lock().unwrap());
+        Ok(GenerationOutput {
+            project,
+            report,
+            generated_suites,
+            task_properties,
+        })
+    }
+
+    /// Names of the build variants to generate, in the order to generate them.
+    fn build_variants(&self) -> Result<Vec<String>> {
+        match &self.scope {
+            GenerationScope::Version => {
+                let mut build_variants: Vec<String> = self
+                    .evg_project
+                    .buildvariants
+                    .iter()
+                    .map(|bv| bv.name.clone())
+                    .filter(|bv| {
+                        let tags = self.variant_tags.get(bv).map(|t| t.as_slice());
+                        self.variant_filter
+                            .includes_build_variant(bv, tags.unwrap_or_default())
+                    })
+                    .collect();
+                build_variants.sort();
+                self.variant_filter.prioritize(&mut build_variants);
+                Ok(build_variants)
+            }
+            GenerationScope::BuildVariant(build_variant) => {
+                self.build_variant(build_variant)?;
+                Ok(vec![build_variant.clone()])
+            }
+        }
+    }
+
+    fn build_variant(&self, bv_name: &str) -> Result<&BuildVariant> {
+        match self
+            .evg_project
+            .buildvariants
+            .iter()
+            .find(|bv| bv.name == bv_name)
+        {
+            Some(build_variant) => Ok(build_variant),
+            None => bail!("Build variant '{}' is not in the project", bv_name),
+        }
+    }
+
+    /// Generators of the task types this generator can generate.
+    ///
+    /// Custom generators are checked before the built in resmoke and fuzzer generators.
+    fn task_type_registry(&self) -> TaskTypeRegistry {
+        let mut registry = TaskTypeRegistry::default();
+        for task_type_generator in &self.task_type_generators {
+            registry.register(task_type_generator.clone());
+        }
+        registry.register(Arc::new(ResmokeTaskGenerator::new(
+            self.task_history_service.clone(),
+            self.task_splitter.clone(),
+            self.previous_generation.clone(),
+        )));
+        registry.register(Arc::new(FuzzerTaskGenerator::new(
+            self.gen_fuzzer_service.clone(),
+        )));
+        registry
+    }
+
+    /// Tasks of a build variant that should be generated, with their generator.
+    fn generated_task_defs(
+        &self,
+        build_variant: &BuildVariant,
+        registry: &TaskTypeRegistry,
+    ) -> Vec<(EvgTask, Arc<dyn TaskTypeGenerator>)> {
+        let task_map = self.evg_project.task_def_map();
+        let generator_functions = self.generator_config.generator_functions();
+        build_variant
+            .tasks
+            .iter()
+            .filter(|t| self.variant_filter.includes_task(&t.name))
+            .filter_map(|t| task_map.get(&t.name).copied())
+            .filter_map(|t| {
+                registry
+                    .find(t, generator_functions)
+                    .map(|task_type_generator| (t.clone(), task_type_generator))
+            })
+            .collect()
+    }
+
+    /// Generate the configuration of a build variant.
+    ///
+    /// # Arguments
+    ///
+    /// * `bv_name` - Name of the build variant.
+    /// * `registry` - Generators of each type of task.
+    /// * `generation_report` - Report to add generated tasks to.
+    async fn generate_build_variant(
+        &self,
+        bv_name: &str,
+        registry: &TaskTypeRegistry,
+        generation_report: Arc<Mutex<GenerationReport>>,
+    ) -> Result<(BuildVariant, GeneratedConfig)> {
+        let build_variant = self.build_variant(bv_name)?;
+        let ctx = Arc::new(TaskGenContext {
+            build_variant: build_variant.clone(),
+            expansions: self.expansions.clone(),
+            config_location: self.config_location.clone(),
+            generator_config: self.generator_config.clone(),
+            requester: self.requester,
+            suite_sink: self.suite_sink.clone(),
+            generation_report,
+        });
+
+        let mut handles = vec![];
+        for (task_def, task_type_generator) in self.generated_task_defs(build_variant, registry) {
+            let extra_properties = self.extra_task_properties.get(&task_def.name);
+            let ctx = ctx.clone();
+            handles.push((
+                extra_properties,
+                tokio::spawn(async move { task_type_generator.generate(&task_def, &ctx).await }),
+            ));
+        }
+
+        let mut gen_config = GeneratedConfig::default();
+        for (extra_properties, handle) in handles {
+            gen_config.add_task(handle.await??, extra_properties);
+        }
+
+        // A build variant with activated tasks must be activated for them to run. A single
+        // build variant is generated from a task already running on it, so is already active.
+        let activate = match self.scope {
+            GenerationScope::Version => Some(
+                self.generator_config
+                    .activation_policy()
+                    .activate_build_variant(self.requester, bv_name)
+                    || gen_config
+                        .gen_task_specs
+                        .iter()
+                        .any(|t| t.activate == Some(true)),
+            ),
+            GenerationScope::BuildVariant(_) => None,
+        };
+        let gen_build_variant = BuildVariant {
+            name: bv_name.to_string(),
+            tasks: gen_config.gen_task_specs.clone(),
+            display_tasks: Some(gen_config.display_tasks.clone()),
+            activate,
+            ..Default::default()
+        };
+        Ok((gen_build_variant, gen_config))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        fingerprint::PreviousGeneration,
+        generator_functions::GeneratorFunctions,
+        resmoke::{MultiversionSuite, ResmokeSuiteConfig},
+        split_tasks::SubSuite,
+        task_history::TaskRuntimeHistory,
+        task_types::fuzzer_tasks::{FuzzerGenTaskParams, FuzzerTask},
+    };
+    use async_trait::async_trait;
+    use rstest::rstest;
+    use shrub_rs::models::commands::{fn_call, EvgCommand};
+
+    struct MockTaskHistoryService {}
+
+    #[async_trait]
+    impl TaskHistoryService for MockTaskHistoryService {
+        async fn get_task_history(
+            &self,
+            task: &str,
+            _variant: &str,
+            suite: &str,
+        ) -> TaskRuntimeHistory {
+            TaskRuntimeHistory {
+                suite_name: suite.to_string(),
+                task_name: task.to_string(),
+                test_map: HashMap::new(),
+            }
+        }
+    }
+
+    struct MockTaskSplitter {}
+
+    impl TaskSplitting for MockTaskSplitter {
+        fn split_task(&self, task_stats: &TaskRuntimeHistory, bv_name: &str) -> GeneratedSuite {
+            GeneratedSuite {
+                task_name: task_stats.task_name.clone(),
+                suite_name: task_stats.suite_name.clone(),
+                build_variant: bv_name.to_string(),
+                sub_suites: (0..2)
+                    .map(|i| SubSuite {
+                        name: format!("{}_{}_{}", task_stats.task_name, i, bv_name),
+                        test_list: vec![format!("jstests/t{}.js", i)],
+                    })
+                    .collect(),
+                discovery_duration_ms: 0,
+                fingerprint: None,
+                suite_config: None,
+            }
+        }
+    }
+
+    struct MockGenFuzzerService {}
+
+    impl GenFuzzerService for MockGenFuzzerService {
+        fn generate_fuzzer_task(&self, _params: &FuzzerGenTaskParams) -> Result<FuzzerTask> {
+            bail!("No fuzzers expected")
+        }
+    }
+
+    #[derive(Default)]
+    struct MockSuiteSink {
+        written_suites: Mutex<Vec<String>>,
+    }
+
+    #[async_trait]
+    impl SuiteFileSink for MockSuiteSink {
+        async fn write_sub_suite(&self, gen_suite: &GeneratedSuite) {
+            self.written_suites
+                .lock()
+                .unwrap()
+                .extend(gen_suite.sub_suites.iter().map(|s| s.name.clone()));
+        }
+
+        async fn write_multiversion_suites(
+            &self,
+            _suite_config: &ResmokeSuiteConfig,
+            _suites: &[MultiversionSuite],
+        ) -> Result<()> {
+            Ok(())
+        }
+
+        async fn copy_suite_files(
+            &self,
+            _previous_generation: &PreviousGeneration,
+            _gen_suite: &GeneratedSuite,
+        ) -> Result<()> {
+            Ok(())
+        }
+
+        async fn flush(&self) {}
+
+        fn config_dir(&self) -> &str {
+            CONFIG_DIR
+        }
+
+        fn has_suite_file(&self, suite_file: &str) -> bool {
+            self.written_suites
+                .lock()
+                .unwrap()
+                .iter()
+                .any(|s| suite_file == format!("{}/{}.yml", CONFIG_DIR, s))
+        }
+    }
+
+    struct MockBenchmarkGenerator {}
+
+    #[async_trait]
+    impl TaskTypeGenerator for MockBenchmarkGenerator {
+        fn detects(&self, task_def: &EvgTask, _generator_functions: &GeneratorFunctions) -> bool {
+            task_def.commands.iter().any(
+                |c| matches!(c, EvgCommand::Function(f) if f.func == "generate benchmark tasks"),
+            )
+        }
+
+        async fn generate(
+            &self,
+            task_def: &EvgTask,
+            ctx: &TaskGenContext,
+        ) -> Result<GeneratedTask> {
+            let task = EvgTask {
+                name: format!(
+                    "{}_{}",
+                    remove_gen_suffix_ref(&task_def.name),
+                    ctx.build_variant.name
+                ),
+                ..Default::default()
+            };
+            Ok(GeneratedTask {
+                task_refs: vec![task.get_reference(None, Some(true))],
+                display_tasks: vec![DisplayTask {
+                    name: remove_gen_suffix_ref(&task_def.name).to_string(),
+                    execution_tasks: vec![task.name.clone()],
+                }],
+                tasks: vec![task],
+                ..Default::default()
+            })
+        }
+    }
+
+    fn build_project() -> EvgProject {
+        let tasks = vec![
+            EvgTask {
+                name: "auth_gen".to_string(),
+                commands: vec![fn_call("generate resmoke tasks")],
+                ..Default::default()
+            },
+            EvgTask {
+                name: "core_gen".to_string(),
+                commands: vec![fn_call("generate resmoke tasks")],
+                ..Default::default()
+            },
+        ];
+        let build_variant = |name: &str| BuildVariant {
+            name: name.to_string(),
+            tasks: tasks
+                .iter()
+                .map(|t| TaskRef {
+                    name: t.name.clone(),
+                    distros: None,
+                    activate: None,
+                })
+                .collect(),
+            ..Default::default()
+        };
+        EvgProject {
+            buildvariants: vec![build_variant("linux"), build_variant("windows")],
+            tasks,
+            ..Default::default()
+        }
+    }
+
+    fn build_generator(
+        scope: GenerationScope,
+        variant_filter: VariantFilter,
+        suite_sink: Arc<MockSuiteSink>,
+    ) -> Generator {
+        Generator::builder()
+            .evg_project(build_project())
+            .scope(scope)
+            .config_location("location")
+            .variant_filter(variant_filter)
+            .task_history_service(Arc::new(MockTaskHistoryService {}))
+            .task_splitter(Arc::new(MockTaskSplitter {}))
+            .gen_fuzzer_service(Arc::new(MockGenFuzzerService {}))
+            .suite_sink(suite_sink)
+            .build()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_version_should_generate_every_build_variant() {
+        let suite_sink = Arc::new(MockSuiteSink::default());
+        let generator = build_generator(
+            GenerationScope::Version,
+            VariantFilter::default(),
+            suite_sink.clone(),
+        );
+
+        let output = generator.generate().await.unwrap();
+
+        let build_variants: Vec<&str> = output
+            .project
+            .buildvariants
+            .iter()
+            .map(|bv| bv.name.as_str())
+            .collect();
+        assert_eq!(build_variants, vec!["linux", "windows"]);
+        assert!(output
+            .project
+            .buildvariants
+            .iter()
+            .all(|bv| bv.activate == Some(false)));
+        assert_eq!(output.project.tasks.len(), 8);
+        assert_eq!(output.generated_suites.len(), 4);
+        assert_eq!(suite_sink.written_suites.lock().unwrap().len(), 8);
+    }
+
+    #[tokio::test]
+    async fn test_build_variant_scope_should_only_generate_build_variant() {
+        let suite_sink = Arc::new(MockSuiteSink::default());
+        let variant_filter = VariantFilter {
+            exclude_tasks: vec![regex::Regex::new("^core").unwrap()],
+            ..Default::default()
+        };
+        let generator = build_generator(
+            GenerationScope::BuildVariant("windows".to_string()),
+            variant_filter,
+            suite_sink.clone(),
+        );
+
+        let output = generator.generate().await.unwrap();
+
+        assert_eq!(output.project.buildvariants.len(), 1);
+        assert_eq!(output.project.buildvariants[0].name, "windows");
+        assert_eq!(output.project.buildvariants[0].activate, None);
+        let mut written_suites = suite_sink.written_suites.lock().unwrap().clone();
+        written_suites.sort();
+        assert_eq!(
+            written_suites,
+            vec!["auth_0_windows".to_string(), "auth_1_windows".to_string()]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_custom_task_types_should_be_generated() {
+        let mut evg_project = build_project();
+        evg_project.tasks.push(EvgTask {
+            name: "bench_gen".to_string(),
+            commands: vec![fn_call("generate benchmark tasks")],
+            ..Default::default()
+        });
+        for build_variant in &mut evg_project.buildvariants {
+            build_variant.tasks.push(TaskRef {
+                name: "bench_gen".to_string(),
+                distros: None,
+                activate: None,
+            });
+        }
+        let generator = Generator::builder()
+            .evg_project(evg_project)
+            .scope(GenerationScope::BuildVariant("linux".to_string()))
+            .config_location("location")
+            .task_history_service(Arc::new(MockTaskHistoryService {}))
+            .task_splitter(Arc::new(MockTaskSplitter {}))
+            .gen_fuzzer_service(Arc::new(MockGenFuzzerService {}))
+            .suite_sink(Arc::new(MockSuiteSink::default()))
+            .task_type_generator(Arc::new(MockBenchmarkGenerator {}))
+            .build()
+            .unwrap();
+
+        let output = generator.generate().await.unwrap();
+
+        let task_names: Vec<&str> = output
+            .project
+            .tasks
+            .iter()
+            .map(|t| t.name.as_str())
+            .collect();
+        assert!(task_names.contains(&"bench_linux"));
+        assert_eq!(task_names.len(), 5);
+        assert_eq!(output.generated_suites.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_sub_tasks_should_inherit_extra_task_properties() {
+        let extra_properties = ExtraTaskProperties {
+            priority: Some(50),
+            allowed_requesters: None,
+        };
+        let generator = Generator::builder()
+            .evg_project(build_project())
+            .extra_task_properties(
+                vec![("auth_gen".to_string(), extra_properties.clone())]
+                    .into_iter()
+                    .collect(),
+            )
+            .scope(GenerationScope::BuildVariant("linux".to_string()))
+            .config_location("location")
+            .task_history_service(Arc::new(MockTaskHistoryService {}))
+            .task_splitter(Arc::new(MockTaskSplitter {}))
+            .gen_fuzzer_service(Arc::new(MockGenFuzzerService {}))
+            .suite_sink(Arc::new(MockSuiteSink::default()))
+            .build()
+            .unwrap();
+
+        let output = generator.generate().await.unwrap();
+
+        let mut inheriting_tasks: Vec<&String> = output.task_properties.keys().collect();
+        inheriting_tasks.sort();
+        assert_eq!(inheriting_tasks, vec!["auth_0_linux", "auth_1_linux"]);
+        assert_eq!(output.task_properties["auth_0_linux"], extra_properties);
+    }
+
+    #[tokio::test]
+    async fn test_unknown_build_variant_should_be_an_error() {
+        let generator = build_generator(
+            GenerationScope::BuildVariant("macos".to_string()),
+            VariantFilter::default(),
+            Arc::new(MockSuiteSink::default()),
+        );
+
+        assert!(generator.generate().await.is_err());
+    }
+
+    #[test]
+    fn test_builder_should_require_services() {
+        let result = Generator::builder()
+            .evg_project(build_project())
+            .scope(GenerationScope::Version)
+            .config_location("location")
+            .build();
+
+        assert!(result.is_err());
+    }
+
+    #[rstest]
+    #[case(
+        GenerationScope::Version,
+        Some("rev/generate_tasks/generated-config-version.tgz")
+    )]
+    #[case(GenerationScope::BuildVariant("linux".to_string()), Some("linux/rev/generate_tasks/auth_gen-build.tgz"))]
+    fn test_config_location(#[case] scope: GenerationScope, #[case] expected: Option<&str>) {
+        let expansions: EvgExpansions = serde_yaml::from_str(
+            r#"
+project: mongodb-mongo-master
+revision: rev
+version_id: version
+build_id: build
+task_name: auth_gen
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(expansions.config_location(&scope).ok().as_deref(), expected);
+    }
+
+    #[test]
+    fn test_missing_config_location_expansions_should_be_an_error() {
+        let expansions: EvgExpansions =
+            serde_yaml::from_str("project: mongodb-mongo-master\nrevision: rev\n").unwrap();
+
+        assert!(expansions
+            .config_location(&GenerationScope::Version)
+            .is_err());
+    }
+}
@@ -0,0 +1,469 @@
+use std::path::Path;
+
+use anyhow::Result;
+use regex::Regex;
+use serde::Deserialize;
+use shrub_rs::models::task::TaskDependency;
+
+use crate::{
+    activation::ActivationPolicy,
+    generator_functions::GeneratorFunctions,
+    split_tasks::{ResmokeGenParams, SplitConfig, SplitStrategy},
+    task_types::fuzzer_tasks::FuzzerGenTaskParams,
+};
+
+/// A task generated sub-tasks should depend on.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Dependency {
+    /// Name of the task depended on.
+    pub name: String,
+    /// Build variant of the task depended on, the sub-task's build variant if not given.
+    pub variant: Option<String>,
+}
+
+impl Dependency {
+    fn task_dependency(&self) -> TaskDependency {
+        TaskDependency {
+            name: self.name.clone(),
+            variant: self.variant.clone(),
+        }
+    }
+}
+
+/// Settings that can be overridden for a task.
+///
+/// Unset settings leave the value from Evergreen expansions or task vars in place.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+pub struct TaskSettings {
+    /// How tests should be divided into sub-suites.
+    pub split_strategy: Option<SplitStrategy>,
+    /// Number of sub-suites to split resmoke tasks into.
+    pub max_sub_suites: Option<usize>,
+    /// Should sub-tasks run on the large distro.
+    pub use_large_distro: Option<bool>,
+    /// Name of the large distro to run on.
+    pub large_distro_name: Option<String>,
+    /// Number of times resmoke should repeat each suite.
+    pub repeat_suites: Option<usize>,
+    /// Execution timeout for each sub-task.
+    pub exec_timeout_secs: Option<u64>,
+    /// Extra arguments added to the end of the task's resmoke arguments.
+    pub resmoke_args: Option<String>,
+    /// Tasks sub-tasks depend on, replacing the dependencies of the `_gen` task.
+    pub depends_on: Option<Vec<Dependency>>,
+    /// Should sub-suites run in a task group that shares setup between them.
+    pub use_task_group: Option<bool>,
+    /// Maximum number of hosts the task group may run on at once, every sub-suite at once
+    /// if not set.
+    pub task_group_max_hosts: Option<u64>,
+}
+
+impl TaskSettings {
+    /// Layer other settings on top of these, preferring values from other.
+    fn merge(&mut self, other: &TaskSettings) {
+        if other.split_strategy.is_some() {
+            self.split_strategy = other.split_strategy;
+        }
+        if other.max_sub_suites.is_some() {
+            self.max_sub_suites = other.max_sub_suites;
+        }
+        if other.use_large_distro.is_some() {
+            self.use_large_distro = other.use_large_distro;
+        }
+        if other.large_distro_name.is_some() {
+            self.large_distro_name = other.large_distro_name.clone();
+        }
+        if other.repeat_suites.is_some() {
+            self.repeat_suites = other.repeat_suites;
+        }
+        if other.exec_timeout_secs.is_some() {
+            self.exec_timeout_secs = other.exec_timeout_secs;
+        }
+        if let Some(resmoke_args) = &other.resmoke_args {
+            self.resmoke_args = Some(append_args(self.resmoke_args.as_deref(), resmoke_args));
+        }
+        if other.depends_on.is_some() {
+            self.depends_on = other.depends_on.clone();
+        }
+        if other.use_task_group.is_some() {
+            self.use_task_group = other.use_task_group;
+        }
+        if other.task_group_max_hosts.is_some() {
+            self.task_group_max_hosts = other.task_group_max_hosts;
+        }
+    }
+
+    fn task_dependencies(&self) -> Option<Vec<TaskDependency>> {
+        self.depends_on
+            .as_ref()
+            .map(|deps| deps.iter().map(|d| d.task_dependency()).collect())
+    }
+
+    /// Build the split configuration for a task from the default configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `default` - Split configuration from Evergreen expansions.
+    pub fn split_config(&self, default: &SplitConfig) -> SplitConfig {
+        SplitConfig {
+            n_suites: self.max_sub_suites.unwrap_or(default.n_suites),
+            strategy: self.split_strategy.unwrap_or(default.strategy),
+        }
+    }
+
+    /// Apply these settings to the parameters of a resmoke task.
+    ///
+    /// # Arguments
+    ///
+    /// * `params` - Parameters built from Evergreen expansions and task vars.
+    pub fn apply_to_resmoke(&self, params: &mut ResmokeGenParams) {
+        if let Some(use_large_distro) = self.use_large_distro {
+            params.use_large_distro = use_large_distro;
+        }
+        if let Some(large_distro_name) = &self.large_distro_name {
+            params.large_distro_name = Some(large_distro_name.clone());
+        }
+        if let Some(repeat_suites) = self.repeat_suites {
+            params.repeat_suites = repeat_suites;
+        }
+        if let Some(use_task_group) = self.use_task_group {
+            params.use_task_group = use_task_group;
+        }
+        if let Some(task_group_max_hosts) = self.task_group_max_hosts {
+            params.task_group_max_hosts = Some(task_group_max_hosts);
+        }
+        if let Some(exec_timeout_secs) = self.exec_timeout_secs {
+            params.exec_timeout_secs = Some(exec_timeout_secs);
+        }
+        if let Some(resmoke_args) = &self.resmoke_args {
+            params.resmoke_args = append_args(Some(&params.resmoke_args), resmoke_args);
+        }
+        if let Some(depends_on) = self.task_dependencies() {
+            params.depends_on = Some(depends_on);
+        }
+    }
+
+    /// Apply these settings to the parameters of a fuzzer task.
+    ///
+    /// Splitting, repeat and task group settings do not apply to fuzzers and are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `params` - Parameters built from Evergreen expansions and task vars.
+    pub fn apply_to_fuzzer(&self, params: &mut FuzzerGenTaskParams) {
+        if let Some(use_large_distro) = self.use_large_distro {
+            params.use_large_distro = Some(use_large_distro);
+        }
+        if let Some(large_distro_name) = &self.large_distro_name {
+            params.large_distro_name = Some(large_distro_name.clone());
+        }
+        if let Some(exec_timeout_secs) = self.exec_timeout_secs {
+            params.exec_timeout_secs = Some(exec_timeout_secs);
+        }
+        if let Some(resmoke_args) = &self.resmoke_args {
+            params.resmoke_args = append_args(Some(&params.resmoke_args), resmoke_args);
+        }
+        if let Some(depends_on) = self.task_dependencies() {
+            params.depends_on = Some(depends_on);
+        }
+    }
+}
+
+fn append_args(base: Option<&str>, extra: &str) -> String {
+    match base {
+        Some(base) if !base.is_empty() => format!("{} {}", base, extra),
+        _ => extra.to_string(),
+    }
+}
+
+/// Settings to apply to tasks matching a task name pattern, suite and/or build variant pattern.
+#[derive(Debug, Clone, Deserialize)]
+struct TaskOverride {
+    /// Regex matched against the task name, without the `_gen` suffix.
+    task: Option<String>,
+    /// Name of the resmoke suite the task runs.
+    suite: Option<String>,
+    /// Regex matched against the name of the build variant the task is generated for.
+    build_variant: Option<String>,
+    #[serde(flatten)]
+    settings: TaskSettings,
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+struct GeneratorConfigFile {
+    #[serde(default)]
+    overrides: Vec<TaskOverride>,
+    #[serde(default)]
+    generator_functions: GeneratorFunctions,
+    #[serde(default)]
+    activation: ActivationPolicy,
+}
+
+/// Per-task and per-suite overrides of how tasks are generated.
+///
+/// Settings are resolved in order of increasing precedence:
+///
+/// 1. Built-in defaults.
+/// 2. Evergreen expansions (max sub-suites, large distro name).
+/// 3. `generate resmoke tasks` vars of the task definition.
+/// 4. Overrides from this configuration, in file order.
+///
+/// An override applies to a task when every selector it specifies (`task`, `suite` and
+/// `build_variant`) matches; an override with no selectors applies to every task. When several overrides
+/// apply, later ones win, except `resmoke_args` which are appended in order.
+///
+/// The functions that mark a task as generated and which generated tasks are activated can
+/// also be configured, see `GeneratorFunctions` and `ActivationPolicy`.
+///
+/// ```yaml
+/// overrides:
+///   - task: "^noPassthrough"
+///     max_sub_suites: 10
+///     split_strategy: test_count
+///   - suite: sharding
+///     use_large_distro: true
+///     exec_timeout_secs: 7200
+///     resmoke_args: "--storageEngineCacheSizeGB=1"
+///     use_task_group: true
+///     task_group_max_hosts: 2
+///   - build_variant: "^enterprise-rhel"
+///     depends_on:
+///       - name: package
+///         variant: enterprise-rhel-80-64-bit
+/// ```
+#[derive(Debug, Clone)]
+struct CompiledOverride {
+    task_pattern: Option<Regex>,
+    build_variant_pattern: Option<Regex>,
+    task_override: TaskOverride,
+}
+
+impl CompiledOverride {
+    fn new(task_override: TaskOverride) -> Result<Self> {
+        Ok(Self {
+            task_pattern: task_override.task.as_deref().map(Regex::new).transpose()?,
+            build_variant_pattern: task_override
+                .build_variant
+                .as_deref()
+                .map(Regex::new)
+                .transpose()?,
+            task_override,
+        })
+    }
+
+    fn matches(&self, task_name: &str, suite_name: &str, build_variant: &str) -> bool {
+        let task_matches = self
+            .task_pattern
+            .as_ref()
+            .map(|p| p.is_match(task_name))
+            .unwrap_or(true);
+        let suite_matches = self
+            .task_override
+            .suite
+            .as_ref()
+            .map(|s| s == suite_name)
+            .unwrap_or(true);
+        let build_variant_matches = self
+            .build_variant_pattern
+            .as_ref()
+            .map(|p| p.is_match(build_variant))
+            .unwrap_or(true);
+        task_matches && suite_matches && build_variant_matches
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct GeneratorConfig {
+    overrides: Vec<CompiledOverride>,
+    generator_functions: GeneratorFunctions,
+    activation_policy: ActivationPolicy,
+}
+
+impl GeneratorConfig {
+    /// Read generator configuration from a yaml file.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - Path to yaml file.
+    pub fn from_yaml_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        Self::from_yaml_str(&contents)
+    }
+
+    /// Parse generator configuration from a yaml string.
+    ///
+    /// # Arguments
+    ///
+    /// * `contents` - Yaml configuration.
+    pub fn from_yaml_str(contents: &str) -> Result<Self> {
+        let config_file: GeneratorConfigFile = serde_yaml::from_str(contents)?;
+        let overrides = config_file
+            .overrides
+            .into_iter()
+            .map(CompiledOverride::new)
+            .collect::<Result<_>>()?;
+        Ok(Self {
+            overrides,
+            generator_functions: config_file.generator_functions,
+            activation_policy: config_file.activation,
+        })
+    }
+
+    /// Functions that mark a task as generated and the kind of task each produces.
+    pub fn generator_functions(&self) -> &GeneratorFunctions {
+        &self.generator_functions
+    }
+
+    /// Policy deciding which generated tasks and build variants are activated.
+    pub fn activation_policy(&self) -> &ActivationPolicy {
+        &self.activation_policy
+    }
+
+    /// Resolve the overridden settings for a task.
+    ///
+    /// # Arguments
+    ///
+    /// * `task_name` - Name of the task, without the `_gen` suffix.
+    /// * `suite_name` - Name of the suite the task runs.
+    /// * `build_variant` - Name of the build variant the task is generated for.
+    pub fn settings_for(
+        &self,
+        task_name: &str,
+        suite_name: &str,
+        build_variant: &str,
+    ) -> TaskSettings {
+        let mut settings = TaskSettings::default();
+        for compiled in &self.overrides {
+            if compiled.matches(task_name, suite_name, build_variant) {
+                settings.merge(&compiled.task_override.settings);
+            }
+        }
+        settings
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"
+overrides:
+  - task: "^noPassthrough"
+    max_sub_suites: 10
+    split_strategy: test_count
+    resmoke_args: "--first"
+  - suite: sharding
+    max_sub_suites: 3
+    use_large_distro: true
+  - task: "^noPassthrough"
+    suite: sharding
+    resmoke_args: "--second"
+"#;
+
+    #[test]
+    fn test_later_overrides_should_take_precedence() {
+        let config = GeneratorConfig::from_yaml_str(CONFIG).unwrap();
+
+        let settings = config.settings_for("noPassthroughWithMongod", "sharding", "linux");
+
+        assert_eq!(
+            settings,
+            TaskSettings {
+                split_strategy: Some(SplitStrategy::TestCount),
+                max_sub_suites: Some(3),
+                use_large_distro: Some(true),
+                resmoke_args: Some("--first --second".to_string()),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_unmatched_task_should_have_no_overrides() {
+        let config = GeneratorConfig::from_yaml_str(CONFIG).unwrap();
+
+        assert_eq!(
+            config.settings_for("auth", "auth", "linux"),
+            TaskSettings::default()
+        );
+    }
+
+    #[test]
+    fn test_overrides_should_be_layered_on_task_params() {
+        let config = GeneratorConfig::from_yaml_str(CONFIG).unwrap();
+        let mut params = ResmokeGenParams {
+            use_large_distro: false,
+            large_distro_name: Some("large".to_string()),
+            require_multiversion_setup: false,
+            repeat_suites: 1,
+            resmoke_args: "--task-arg".to_string(),
+            resmoke_jobs_max: None,
+            config_location: None,
+            exec_timeout_secs: None,
+            inherited_properties: Default::default(),
+            depends_on: None,
+            compile_variant: None,
+            use_task_group: false,
+            task_group_max_hosts: None,
+            activate: false,
+        };
+        let default_split = SplitConfig {
+            n_suites: 5,
+            strategy: SplitStrategy::Runtime,
+        };
+
+        let settings = config.settings_for("noPassthrough", "no_passthrough", "linux");
+        settings.apply_to_resmoke(&mut params);
+        let split_config = settings.split_config(&default_split);
+
+        assert_eq!(params.resmoke_args, "--task-arg --first");
+        assert!(!params.use_large_distro);
+        assert_eq!(split_config.n_suites, 10);
+        assert_eq!(split_config.strategy, SplitStrategy::TestCount);
+    }
+
+    #[test]
+    fn test_build_variant_override_should_replace_dependencies() {
+        let config = GeneratorConfig::from_yaml_str(
+            r#"
+overrides:
+  - build_variant: "^enterprise-"
+    depends_on:
+      - name: package
+        variant: packager
+"#,
+        )
+        .unwrap();
+
+        let enterprise = config.settings_for("auth", "auth", "enterprise-rhel");
+        let other = config.settings_for("auth", "auth", "linux");
+
+        assert_eq!(
+            enterprise.depends_on,
+            Some(vec![Dependency {
+                name: "package".to_string(),
+                variant: Some("packager".to_string()),
+            }])
+        );
+        assert_eq!(other.depends_on, None);
+    }
+
+    #[test]
+    fn test_generator_functions_should_default_when_not_configured() {
+        let config = GeneratorConfig::from_yaml_str(CONFIG).unwrap();
+        let custom = GeneratorConfig::from_yaml_str(
+            "generator_functions:\n  - function: generate tasks\n    kind: resmoke\n",
+        )
+        .unwrap();
+
+        assert_eq!(config.generator_functions(), &GeneratorFunctions::default());
+        assert_ne!(custom.generator_functions(), &GeneratorFunctions::default());
+    }
+
+    #[test]
+    fn test_invalid_task_pattern_should_be_an_error() {
+        let config = GeneratorConfig::from_yaml_str("overrides:\n  - task: \"(\"\n");
+
+        assert!(config.is_err());
+    }
+}
@@ -0,0 +1,265 @@
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use shrub_rs::models::{
+    commands::{EvgCommand, FunctionCall},
+    params::ParamValue,
+    task::EvgTask,
+};
+
+use crate::{expansions::Expansions, taskname::remove_gen_suffix_ref};
+
+const DEFAULT_GENERATE_FUNCTION: &str = "generate resmoke tasks";
+
+/// Kind of task a generator function call produces.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TaskKind {
+    /// Resmoke suite split into sub-suites.
+    Resmoke,
+    /// Fuzzer generating a fixed number of sub-tasks.
+    Fuzzer,
+}
+
+/// Rule mapping a call of a generator function to a task kind.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct TaskKindRule {
+    /// Name of the generator function.
+    pub function: String,
+    /// Var of the function call that must be set for the rule to apply.
+    pub var: Option<String>,
+    /// Value the var must have, any value if not given.
+    pub value: Option<String>,
+    /// Kind of task produced when the rule applies.
+    pub kind: TaskKind,
+}
+
+impl TaskKindRule {
+    fn matches(&self, func: &FunctionCall) -> bool {
+        if func.func != self.function {
+            return false;
+        }
+        let var = match &self.var {
+            Some(var) => var,
+            None => return true,
+        };
+        let value = func.vars.as_ref().and_then(|vars| vars.get(var));
+        match (value, &self.value) {
+            (None, _) => false,
+            (Some(_), None) => true,
+            (Some(value), Some(expected)) => param_to_string(value).as_deref() == Some(expected),
+        }
+    }
+}
+
+fn param_to_string(value: &ParamValue) -> Option<String> {
+    match value {
+        ParamValue::String(value) => Some(value.clone()),
+        ParamValue::Bool(value) => Some(value.to_string()),
+        ParamValue::Number(value) => Some(value.to_string()),
+        _ => None,
+    }
+}
+
+/// Functions that mark a task as generated and the kind of task each produces.
+///
+/// Rules are checked in order and the first matching rule determines the task kind, so
+/// rules with a var condition should come before a catch-all rule for the same function.
+///
+/// ```yaml
+/// generator_functions:
+///   - function: generate resmoke tasks
+///     var: is_jstestfuzz
+///     value: "true"
+///     kind: fuzzer
+///   - function: generate resmoke tasks
+///     kind: resmoke
+/// ```
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(transparent)]
+pub struct GeneratorFunctions {
+    rules: Vec<TaskKindRule>,
+}
+
+impl Default for GeneratorFunctions {
+    fn default() -> Self {
+        Self {
+            rules: vec![
+                TaskKindRule {
+                    function: DEFAULT_GENERATE_FUNCTION.to_string(),
+                    var: Some("is_jstestfuzz".to_string()),
+                    value: Some("true".to_string()),
+                    kind: TaskKind::Fuzzer,
+                },
+                TaskKindRule {
+                    function: DEFAULT_GENERATE_FUNCTION.to_string(),
+                    var: None,
+                    value: None,
+                    kind: TaskKind::Resmoke,
+                },
+            ],
+        }
+    }
+}
+
+impl GeneratorFunctions {
+    /// Create generator functions from a list of rules.
+    pub fn new(rules: Vec<TaskKindRule>) -> Self {
+        Self { rules }
+    }
+
+    fn is_generator_function(&self, name: &str) -> bool {
+        self.rules.iter().any(|r| r.function == name)
+    }
+
+    /// Get the generator function call of a task, if it has one.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - Task definition to check.
+    pub fn generate_func<'a>(&self, task: &'a EvgTask) -> Option<&'a FunctionCall> {
+        task.commands.iter().find_map(|c| match c {
+            EvgCommand::Function(func) if self.is_generator_function(&func.func) => Some(func),
+            _ => None,
+        })
+    }
+
+    /// Get the kind of task a task definition generates, if it is generated.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - Task definition to check.
+    pub fn task_kind(&self, task: &EvgTask) -> Option<TaskKind> {
+        let func = self.generate_func(task)?;
+        self.rules.iter().find(|r| r.matches(func)).map(|r| r.kind)
+    }
+
+    /// Check if a task is generated.
+    pub fn is_task_generated(&self, task: &EvgTask) -> bool {
+        self.task_kind(task).is_some()
+    }
+
+    /// Check if a task generates a fuzzer.
+    pub fn is_fuzzer_task(&self, task: &EvgTask) -> bool {
+        self.task_kind(task) == Some(TaskKind::Fuzzer)
+    }
+
+    /// Get a string var of the generator function call of a task.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - Task definition to get var from.
+    /// * `var` - Name of var to get.
+    pub fn get_gen_task_var<'a>(&self, task: &'a EvgTask, var: &str) -> Option<&'a str> {
+        match self.generate_func(task)?.vars.as_ref()?.get(var) {
+            Some(ParamValue::String(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Get the resmoke suite a generated task runs, defaulting to the task name.
+    pub fn find_suite_name<'a>(&self, task: &'a EvgTask) -> &'a str {
+        self.get_gen_task_var(task, "suite")
+            .unwrap_or_else(|| remove_gen_suffix_ref(&task.name))
+    }
+
+    /// Get the resmoke suite a generated task runs, with expansions in its name resolved.
+    ///
+    /// # Arguments
+    ///
+    /// * `task` - Task definition to get suite of.
+    /// * `expansions` - Expansions to resolve references in the suite name with.
+    pub fn find_expanded_suite_name(
+        &self,
+        task: &EvgTask,
+        expansions: &Expansions,
+    ) -> Result<String> {
+        expansions
+            .expand(self.find_suite_name(task))
+            .with_context(|| format!("Could not expand suite of task '{}'", task.name))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maplit::hashmap;
+    use rstest::rstest;
+    use shrub_rs::models::commands::{fn_call, fn_call_with_params};
+
+    fn build_task(func: &str, var: Option<(&str, ParamValue)>) -> EvgTask {
+        let vars = var
+            .map(|(name, value)| hashmap! { name.to_string() => value })
+            .unwrap_or_default();
+        EvgTask {
+            name: "task_gen".to_string(),
+            commands: vec![fn_call("do setup"), fn_call_with_params(func, vars)],
+            ..Default::default()
+        }
+    }
+
+    #[rstest]
+    #[case("generate resmoke tasks", None, Some(TaskKind::Resmoke))]
+    #[case("generate resmoke tasks", Some(("is_jstestfuzz", ParamValue::from("true"))), Some(TaskKind::Fuzzer))]
+    #[case("generate resmoke tasks", Some(("is_jstestfuzz", ParamValue::from(true))), Some(TaskKind::Fuzzer))]
+    #[case("generate resmoke tasks", Some(("is_jstestfuzz", ParamValue::from("false"))), Some(TaskKind::Resmoke))]
+    #[case("run tests", None, None)]
+    fn test_default_task_kinds(
+        #[case] func: &str,
+        #[case] var: Option<(&str, ParamValue)>,
+        #[case] expected: Option<TaskKind>,
+    ) {
+        let task = build_task(func, var);
+
+        assert_eq!(GeneratorFunctions::default().task_kind(&task), expected);
+    }
+
+    #[test]
+    fn test_custom_functions_should_be_detected() {
+        let functions: GeneratorFunctions = serde_yaml::from_str(
+            r#"
+- function: generate fuzzer
+  kind: fuzzer
+- function: generate tests
+  var: suite
+  kind: resmoke
+"#,
+        )
+        .unwrap();
+
+        let fuzzer = build_task("generate fuzzer", None);
+        let resmoke = build_task("generate tests", Some(("suite", ParamValue::from("core"))));
+        let no_suite = build_task("generate tests", None);
+        let default_func = build_task("generate resmoke tasks", None);
+
+        assert_eq!(functions.task_kind(&fuzzer), Some(TaskKind::Fuzzer));
+        assert_eq!(functions.task_kind(&resmoke), Some(TaskKind::Resmoke));
+        assert_eq!(functions.find_suite_name(&resmoke), "core");
+        assert_eq!(functions.task_kind(&no_suite), None);
+        assert!(!functions.is_task_generated(&default_func));
+    }
+
+    #[test]
+    fn test_suite_name_should_be_expanded() {
+        let task = build_task(
+            "generate resmoke tasks",
+            Some(("suite", ParamValue::from("${suite_name|core}"))),
+        );
+        let expansions = Expansions::new(hashmap! {
+            "suite_name".to_string() => "sharding".to_string(),
+        });
+        let functions = GeneratorFunctions::default();
+
+        assert_eq!(
+            functions
+                .find_expanded_suite_name(&task, &expansions)
+                .unwrap(),
+            "sharding"
+        );
+        assert_eq!(
+            functions
+                .find_expanded_suite_name(&task, &Expansions::default())
+                .unwrap(),
+            "core"
+        );
+    }
+}
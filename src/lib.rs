@@ -1,101 +1,210 @@
 use std::path::Path;
 use std::{error::Error, process::Command};
 
-use shrub_rs::models::commands::EvgCommand::Function;
-use shrub_rs::models::{
-    commands::FunctionCall, params::ParamValue, project::EvgProject, task::EvgTask,
-};
-use taskname::remove_gen_suffix_ref;
+use anyhow::Context;
+use expansions::Expansions;
+use generator_functions::GeneratorFunctions;
+use lazy_static::lazy_static;
+use shrub_rs::models::{commands::FunctionCall, project::EvgProject, task::EvgTask};
 
+pub mod activation;
+pub mod archive;
+pub mod diff;
+pub mod expansions;
+pub mod fingerprint;
+pub mod gen_vars;
+pub mod generator;
+pub mod generator_config;
+pub mod generator_functions;
+pub mod lint;
+pub mod output;
+pub mod report;
 pub mod resmoke;
-pub mod resmoke_task_gen;
 pub mod split_tasks;
 pub mod task_history;
+pub mod task_properties;
 pub mod task_types;
 pub mod taskname;
 pub mod util;
+pub mod validate;
+pub mod variant_filter;
 pub mod write_config;
 
-#[derive(Clone, Debug)]
-pub struct SubSuite {
-    pub index: usize,
-    pub suite_name: String,
-    pub test_list: Vec<String>,
-}
+const MULTIVERSION_FILTER_SUFFIX: &str = "_only";
 
-#[derive(Clone, Debug)]
-pub struct GeneratedSuite {
-    pub sub_suites: Vec<SubSuite>,
-    pub build_variant: String,
-    pub task_name: String,
-    pub suite_name: String,
-    pub filename: String,
-    pub include_build_variant_in_name: bool,
+lazy_static! {
+    static ref DEFAULT_GENERATOR_FUNCTIONS: GeneratorFunctions = GeneratorFunctions::default();
 }
 
 pub fn get_project_config(location: &Path) -> Result<EvgProject, Box<dyn Error>> {
+    EvgProject::from_yaml_str(&get_project_config_yaml(location)?)
+}
+
+/// Get the evaluated project configuration at the given location as YAML.
+pub fn get_project_config_yaml(location: &Path) -> Result<String, Box<dyn Error>> {
     let evg_config_yaml = Command::new("evergreen")
         .args(&["evaluate", location.to_str().unwrap()])
         .output()?;
-    EvgProject::from_yaml_str(std::str::from_utf8(&evg_config_yaml.stdout)?)
+    Ok(std::str::from_utf8(&evg_config_yaml.stdout)?.to_string())
 }
 
+/// Check if a task is generated, using the default generator functions.
 pub fn is_task_generated(task: &EvgTask) -> bool {
-    task.commands.iter().any(|c| {
-        if let Function(func) = c {
-            if func.func == "generate resmoke tasks" {
-                return true;
-            }
-        }
-        false
-    })
+    DEFAULT_GENERATOR_FUNCTIONS.is_task_generated(task)
 }
 
+/// Get the generator function call of a task, using the default generator functions.
 pub fn get_generate_resmoke_func(task: &EvgTask) -> Option<&FunctionCall> {
-    let command = task
-        .commands
-        .iter()
-        .find(|c| {
-            if let Function(func) = c {
-                if func.func == "generate resmoke tasks" {
-                    return true;
-                }
-            }
-            false
-        })
-        .unwrap();
-    if let Function(func) = command {
-        return Some(func);
-    }
-    None
+    DEFAULT_GENERATOR_FUNCTIONS.generate_func(task)
 }
 
+/// Get a string var of the generator function call of a task, using the default generator
+/// functions.
 pub fn get_gen_task_var<'a>(task: &'a EvgTask, var: &str) -> Option<&'a str> {
-    let generate_func = get_generate_resmoke_func(task);
-    if let Some(func) = generate_func {
-        if let Some(vars) = &func.vars {
-            if let Some(ParamValue::String(value)) = vars.get(var) {
-                return Some(value);
-            }
-        }
-    }
-    None
+    DEFAULT_GENERATOR_FUNCTIONS.get_gen_task_var(task, var)
 }
 
+/// Get the resmoke suite a generated task runs, using the default generator functions.
 pub fn find_suite_name(task: &EvgTask) -> &str {
-    let suite = get_gen_task_var(task, "suite");
-    if let Some(suite) = suite {
-        suite
+    DEFAULT_GENERATOR_FUNCTIONS.find_suite_name(task)
+}
+
+/// Get the old versions a multiversion task is restricted to, if any.
+///
+/// Tasks are restricted with the `multiversion_versions` gen var, a comma separated list of
+/// versions, or by tagging the task with `<version>_only` (i.e. `last_lts_only`).
+///
+/// # Arguments
+///
+/// * `task` - Task definition to check.
+/// * `generator_functions` - Functions that mark a task as generated.
+/// * `expansions` - Expansions to resolve references in the `multiversion_versions` var with.
+pub fn get_multiversion_filter(
+    task: &EvgTask,
+    generator_functions: &GeneratorFunctions,
+    expansions: &Expansions,
+) -> anyhow::Result<Option<Vec<String>>> {
+    if let Some(versions) = generator_functions.get_gen_task_var(task, "multiversion_versions") {
+        let versions = expansions.expand(versions).with_context(|| {
+            format!(
+                "Could not expand var 'multiversion_versions' of task '{}'",
+                task.name
+            )
+        })?;
+        return Ok(Some(
+            versions
+                .split(',')
+                .map(|v| v.trim().to_string())
+                .filter(|v| !v.is_empty())
+                .collect(),
+        ));
+    }
+
+    let versions: Vec<String> = task
+        .tags
+        .as_ref()
+        .map(|tags| {
+            tags.iter()
+                .filter(|t| t.starts_with("last_") && t.ends_with(MULTIVERSION_FILTER_SUFFIX))
+                .map(|t| t.trim_end_matches(MULTIVERSION_FILTER_SUFFIX).to_string())
+                .collect()
+        })
+        .unwrap_or_default();
+    if versions.is_empty() {
+        Ok(None)
     } else {
-        remove_gen_suffix_ref(&task.name)
+        Ok(Some(versions))
     }
 }
 
+/// Check if a task generates a fuzzer, using the default generator functions.
 pub fn is_fuzzer_task(task: &EvgTask) -> bool {
-    let is_jstestfuzz = get_gen_task_var(task, "is_jstestfuzz");
-    if let Some(is_jstestfuzz) = is_jstestfuzz {
-        is_jstestfuzz == "true"
-    } else {
-        false
+    DEFAULT_GENERATOR_FUNCTIONS.is_fuzzer_task(task)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maplit::hashmap;
+    use shrub_rs::models::{commands::fn_call_with_params, params::ParamValue};
+
+    fn build_gen_task(tags: Option<Vec<&str>>, versions_var: Option<&str>) -> EvgTask {
+        let mut vars = hashmap! {
+            "is_jstestfuzz".to_string() => ParamValue::from("true"),
+        };
+        if let Some(versions) = versions_var {
+            vars.insert(
+                "multiversion_versions".to_string(),
+                ParamValue::from(versions),
+            );
+        }
+        EvgTask {
+            name: "my_task_gen".to_string(),
+            commands: vec![fn_call_with_params("generate resmoke tasks", vars)],
+            tags: tags.map(|t| t.iter().map(|t| t.to_string()).collect()),
+            ..Default::default()
+        }
+    }
+
+    // get_multiversion_filter tests.
+    #[test]
+    fn test_task_without_filter_should_return_none() {
+        let task = build_gen_task(Some(vec!["multiversion"]), None);
+
+        assert_eq!(
+            get_multiversion_filter(
+                &task,
+                &GeneratorFunctions::default(),
+                &Expansions::default()
+            )
+            .unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_task_with_only_tag_should_filter_versions() {
+        let task = build_gen_task(Some(vec!["multiversion", "last_lts_only"]), None);
+
+        assert_eq!(
+            get_multiversion_filter(
+                &task,
+                &GeneratorFunctions::default(),
+                &Expansions::default()
+            )
+            .unwrap(),
+            Some(vec!["last_lts".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_gen_var_should_take_precedence_over_tags() {
+        let task = build_gen_task(
+            Some(vec!["last_lts_only"]),
+            Some("last_lts, last_continuous"),
+        );
+
+        assert_eq!(
+            get_multiversion_filter(
+                &task,
+                &GeneratorFunctions::default(),
+                &Expansions::default()
+            )
+            .unwrap(),
+            Some(vec!["last_lts".to_string(), "last_continuous".to_string()])
+        );
+    }
+
+    #[test]
+    fn test_gen_var_should_be_expanded() {
+        let task = build_gen_task(None, Some("${versions|last_lts}"));
+        let expansions = Expansions::new(hashmap! {
+            "versions".to_string() => "last_continuous".to_string(),
+        });
+
+        assert_eq!(
+            get_multiversion_filter(&task, &GeneratorFunctions::default(), &expansions).unwrap(),
+            Some(vec!["last_continuous".to_string()])
+        );
     }
 }
@@ -0,0 +1,329 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+
+use anyhow::Result;
+use lazy_static::lazy_static;
+use regex::Regex;
+use shrub_rs::models::{project::EvgProject, task::EvgTask, variant::BuildVariant};
+
+use crate::{
+    expansions::Expansions,
+    gen_vars::{FuzzerGenVars, ResmokeGenVars},
+    generator_functions::GeneratorFunctions,
+    resmoke::{ResmokeSuiteConfig, VersionCombinationConfig},
+    task_properties::compile_variant,
+};
+
+lazy_static! {
+    /// An npm script name, without arguments.
+    static ref NPM_COMMAND_RE: Regex = Regex::new(r"^[A-Za-z0-9][A-Za-z0-9_:.-]*$").unwrap();
+}
+
+const MULTIVERSION_TAG: &str = "multiversion";
+
+/// A problem found in the definition of a generated task.
+#[derive(Debug, Clone, PartialEq, PartialOrd, Eq, Ord)]
+pub struct LintProblem {
+    /// Build variant the task was checked for, if it runs on any.
+    pub build_variant: Option<String>,
+    pub task: String,
+    pub message: String,
+}
+
+impl Display for LintProblem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match &self.build_variant {
+            Some(build_variant) => write!(f, "{}: {}: {}", build_variant, self.task, self.message),
+            None => write!(f, "{}: {}", self.task, self.message),
+        }
+    }
+}
+
+/// Check the definitions of all generated tasks in a project.
+///
+/// Each task is checked for every build variant it runs on, since its vars may reference
+/// build variant expansions. All problems found are returned, sorted by build variant and task.
+///
+/// # Arguments
+///
+/// * `project` - Evergreen project to check.
+/// * `generator_functions` - Functions that mark a task as generated.
+/// * `expansions` - Expansions available to every build variant.
+/// * `version_combinations` - Version combinations multiversion fixtures are generated with.
+/// * `load_suite` - Function to load the configuration of a resmoke suite.
+pub fn lint_project<F>(
+    project: &EvgProject,
+    generator_functions: &GeneratorFunctions,
+    expansions: &Expansions,
+    version_combinations: &VersionCombinationConfig,
+    load_suite: F,
+) -> Vec<LintProblem>
+where
+    F: Fn(&str) -> Result<ResmokeSuiteConfig>,
+{
+    let task_map = project.task_def_map();
+    let build_variant_map = project.build_variant_map();
+    let mut suite_cache: HashMap<String, std::result::Result<ResmokeSuiteConfig, String>> =
+        HashMap::new();
+    let mut linter = |task_def: &EvgTask, build_variant: Option<&BuildVariant>| {
+        let expansions = match build_variant {
+            Some(build_variant) => expansions.for_build_variant(build_variant),
+            None => expansions.clone(),
+        };
+        // A suite name that fails to expand is reported with the other gen vars.
+        let suite = generator_functions
+            .find_expanded_suite_name(task_def, &expansions)
+            .unwrap_or_else(|_| generator_functions.find_suite_name(task_def).to_string());
+        let suite_config = suite_cache
+            .entry(suite.clone())
+            .or_insert_with(|| load_suite(&suite).map_err(|e| format!("{:#}", e)))
+            .clone();
+        lint_task(
+            task_def,
+            build_variant,
+            generator_functions,
+            &expansions,
+            &suite,
+            suite_config,
+            version_combinations,
+        )
+    };
+
+    let mut problems = vec![];
+    let mut linted_tasks = HashSet::new();
+    for build_variant in &project.buildvariants {
+        let compile_variant_error =
+            compile_variant(build_variant, expansions, &build_variant_map).err();
+        for task_ref in &build_variant.tasks {
+            if let Some(task_def) = task_map.get(&task_ref.name) {
+                if generator_functions.is_task_generated(task_def) {
+                    problems.extend(linter(task_def, Some(build_variant)));
+                    if let Some(error) = &compile_variant_error {
+                        problems.push(LintProblem {
+                            build_variant: Some(build_variant.name.clone()),
+                            task: task_def.name.clone(),
+                            message: format!("{:#}", error),
+                        });
+                    }
+                    linted_tasks.insert(task_def.name.clone());
+                }
+            }
+        }
+    }
+    for task_def in &project.tasks {
+        if generator_functions.is_task_generated(task_def) && !linted_tasks.contains(&task_def.name)
+        {
+            problems.extend(linter(task_def, None));
+        }
+    }
+
+    problems.sort();
+    problems
+}
+
+fn lint_task(
+    task_def: &EvgTask,
+    build_variant: Option<&BuildVariant>,
+    generator_functions: &GeneratorFunctions,
+    expansions: &Expansions,
+    suite: &str,
+    suite_config: std::result::Result<ResmokeSuiteConfig, String>,
+    version_combinations: &VersionCombinationConfig,
+) -> Vec<LintProblem> {
+    let mut messages = vec![];
+
+    if generator_functions.is_fuzzer_task(task_def) {
+        match FuzzerGenVars::from_task(task_def, generator_functions, expansions) {
+            Ok(vars) => {
+                if !NPM_COMMAND_RE.is_match(&vars.npm_command) {
+                    messages.push(format!("invalid npm_command '{}'", vars.npm_command));
+                }
+            }
+            Err(error) => messages.push(format!("{:#}", error)),
+        }
+    } else if let Err(error) = ResmokeGenVars::from_task(task_def, generator_functions, expansions)
+    {
+        messages.push(format!("{:#}", error));
+    }
+
+    match suite_config {
+        Ok(suite_config) => {
+            let is_multiversion = task_def
+                .tags
+                .as_ref()
+                .map(|tags| tags.iter().any(|t| t == MULTIVERSION_TAG))
+                .unwrap_or(false);
+            if is_multiversion {
+                match suite_config.get_fixture_type() {
+                    Ok(fixture_type) => {
+                        if !version_combinations.has_combinations(&fixture_type) {
+                            messages.push(format!(
+                                "multiversion suite '{}' has no version combinations for its fixture",
+                                suite
+                            ));
+                        }
+                    }
+                    Err(error) => messages.push(format!(
+                        "could not determine fixture type of multiversion suite '{}': {}",
+                        suite, error
+                    )),
+                }
+            }
+        }
+        Err(error) => messages.push(format!("could not load suite '{}': {}", suite, error)),
+    }
+
+    messages
+        .into_iter()
+        .map(|message| LintProblem {
+            build_variant: build_variant.map(|bv| bv.name.clone()),
+            task: task_def.name.clone(),
+            message,
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::bail;
+    use maplit::hashmap;
+    use shrub_rs::models::{commands::fn_call_with_params, params::ParamValue, task::TaskRef};
+    use std::str::FromStr;
+
+    const REPL_SUITE: &str =
+        "executor:\n  fixture:\n    class: ReplicaSetFixture\n    num_nodes: 3\n";
+    const STANDALONE_SUITE: &str = "executor:\n  fixture:\n    class: MongoDFixture\n";
+    const NO_EXECUTOR_SUITE: &str = "test_kind: js_test\n";
+
+    fn load_suite(suite: &str) -> Result<ResmokeSuiteConfig> {
+        match suite {
+            "repl_suite" => Ok(ResmokeSuiteConfig::from_str(REPL_SUITE)?),
+            "standalone_suite" => Ok(ResmokeSuiteConfig::from_str(STANDALONE_SUITE)?),
+            "no_executor_suite" => Ok(ResmokeSuiteConfig::from_str(NO_EXECUTOR_SUITE)?),
+            _ => bail!("Suite '{}' not found", suite),
+        }
+    }
+
+    fn build_task(name: &str, vars: HashMap<String, ParamValue>, tags: &[&str]) -> EvgTask {
+        EvgTask {
+            name: name.to_string(),
+            commands: vec![fn_call_with_params("generate resmoke tasks", vars)],
+            tags: Some(tags.iter().map(|t| t.to_string()).collect()),
+            ..Default::default()
+        }
+    }
+
+    fn fuzzer_vars(suite: &str, npm_command: &str) -> HashMap<String, ParamValue> {
+        hashmap! {
+            "is_jstestfuzz".to_string() => ParamValue::from("true"),
+            "suite".to_string() => ParamValue::from(suite),
+            "num_files".to_string() => ParamValue::from("${num_files}"),
+            "num_tasks".to_string() => ParamValue::from(5),
+            "resmoke_args".to_string() => ParamValue::from(""),
+            "npm_command".to_string() => ParamValue::from(npm_command),
+            "continue_on_failure".to_string() => ParamValue::from(false),
+            "resmoke_jobs_max".to_string() => ParamValue::from(1),
+            "should_shuffle".to_string() => ParamValue::from(false),
+            "timeout_secs".to_string() => ParamValue::from(1800),
+        }
+    }
+
+    fn build_project(tasks: Vec<EvgTask>) -> EvgProject {
+        EvgProject {
+            buildvariants: vec![BuildVariant {
+                name: "bv".to_string(),
+                tasks: tasks
+                    .iter()
+                    .map(|t| TaskRef {
+                        name: t.name.clone(),
+                        distros: None,
+                        activate: None,
+                    })
+                    .collect(),
+                expansions: Some(hashmap! {
+                    "num_files".to_string() => "10".to_string(),
+                }),
+                ..Default::default()
+            }],
+            tasks,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_valid_tasks_should_have_no_problems() {
+        let project = build_project(vec![
+            build_task(
+                "fuzzer_gen",
+                fuzzer_vars("repl_suite", "agg-fuzzer"),
+                &["multiversion"],
+            ),
+            build_task(
+                "resmoke_gen",
+                hashmap! {"suite".to_string() => ParamValue::from("standalone_suite")},
+                &[],
+            ),
+        ]);
+
+        let problems = lint_project(
+            &project,
+            &GeneratorFunctions::default(),
+            &Expansions::default(),
+            &VersionCombinationConfig::default(),
+            load_suite,
+        );
+
+        assert_eq!(problems, vec![]);
+    }
+
+    #[test]
+    fn test_all_problems_should_be_reported() {
+        let mut missing_vars = fuzzer_vars("repl_suite", "jstestfuzz");
+        missing_vars.remove("num_tasks");
+        let project = build_project(vec![
+            build_task("bad_npm_gen", fuzzer_vars("repl_suite", "npm run x"), &[]),
+            build_task("missing_vars_gen", missing_vars, &[]),
+            build_task(
+                "missing_suite_gen",
+                hashmap! {"suite".to_string() => ParamValue::from("nope")},
+                &[],
+            ),
+            build_task(
+                "multiversion_gen",
+                hashmap! {"suite".to_string() => ParamValue::from("standalone_suite")},
+                &["multiversion"],
+            ),
+            build_task(
+                "no_executor_gen",
+                hashmap! {"suite".to_string() => ParamValue::from("no_executor_suite")},
+                &["multiversion"],
+            ),
+        ]);
+
+        let problems = lint_project(
+            &project,
+            &GeneratorFunctions::default(),
+            &Expansions::default(),
+            &VersionCombinationConfig::default(),
+            load_suite,
+        );
+
+        let tasks: Vec<&str> = problems.iter().map(|p| p.task.as_str()).collect();
+        assert_eq!(
+            tasks,
+            vec![
+                "bad_npm_gen",
+                "missing_suite_gen",
+                "missing_vars_gen",
+                "multiversion_gen",
+                "no_executor_gen"
+            ]
+        );
+        assert!(problems
+            .iter()
+            .all(|p| p.build_variant == Some("bv".to_string())));
+        assert!(problems[2].message.contains("num_tasks"));
+        assert!(problems[4].message.contains("suite has no executor"));
+    }
+}
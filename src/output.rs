@@ -0,0 +1,738 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{bail, Result};
+use serde::Serialize;
+use shrub_rs::models::{
+    project::EvgProject,
+    task::{EvgTask, TaskRef},
+    task_group::EvgTaskGroup,
+    variant::{BuildVariant, DisplayTask},
+};
+
+use crate::task_properties::ExtraTaskProperties;
+
+/// Format to write generated configuration in.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    /// Compact json.
+    Json,
+    /// Indented json.
+    JsonPretty,
+    /// Yaml, for human review.
+    Yaml,
+}
+
+impl FromStr for OutputFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "json" => Ok(Self::Json),
+            "json-pretty" => Ok(Self::JsonPretty),
+            "yaml" => Ok(Self::Yaml),
+            _ => bail!(
+                "Unknown output format '{}', expected one of: json, json-pretty, yaml",
+                s
+            ),
+        }
+    }
+}
+
+impl OutputFormat {
+    /// File extension for files written in this format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Self::Json | Self::JsonPretty => "json",
+            Self::Yaml => "yml",
+        }
+    }
+
+    /// Serialize the given value in this format.
+    ///
+    /// Maps are written with their keys sorted so identical values produce identical output.
+    pub fn serialize<T: Serialize>(&self, value: &T) -> Result<String> {
+        let value = serde_json::to_value(value)?;
+        Ok(match self {
+            Self::Json => serde_json::to_string(&value)?,
+            Self::JsonPretty => serde_json::to_string_pretty(&value)?,
+            Self::Yaml => serde_yaml::to_string(&value)?,
+        })
+    }
+}
+
+/// Sort the contents of a generated configuration so it does not depend on generation order.
+///
+/// Build variants, tasks, task groups, task references and display tasks are sorted by name.
+/// The order of execution tasks within a display task or task group is preserved.
+pub fn sort_config(project: &mut EvgProject) {
+    project.buildvariants.sort_by(|a, b| a.name.cmp(&b.name));
+    project.tasks.sort_by(|a, b| a.name.cmp(&b.name));
+    if let Some(task_groups) = project.task_groups.as_mut() {
+        task_groups.sort_by(|a, b| a.name.cmp(&b.name));
+    }
+    for build_variant in project.buildvariants.iter_mut() {
+        build_variant.tasks.sort_by(|a, b| a.name.cmp(&b.name));
+        if let Some(display_tasks) = build_variant.display_tasks.as_mut() {
+            display_tasks.sort_by(|a, b| a.name.cmp(&b.name));
+        }
+    }
+}
+
+/// Serialize a generated configuration, adding the extra properties of its tasks.
+///
+/// # Arguments
+///
+/// * `project` - Generated configuration to serialize.
+/// * `task_properties` - Extra properties of generated tasks, by task name.
+fn project_value(
+    project: &EvgProject,
+    task_properties: &HashMap<String, ExtraTaskProperties>,
+) -> Result<serde_json::Value> {
+    let mut value = serde_json::to_value(project)?;
+    let tasks = value
+        .get_mut("tasks")
+        .and_then(|tasks| tasks.as_array_mut());
+    for task in tasks.into_iter().flatten() {
+        let properties = task
+            .get("name")
+            .and_then(|name| name.as_str())
+            .and_then(|name| task_properties.get(name));
+        if let (Some(properties), Some(task)) = (properties, task.as_object_mut()) {
+            if let serde_json::Value::Object(properties) = serde_json::to_value(properties)? {
+                task.extend(properties);
+            }
+        }
+    }
+    Ok(value)
+}
+
+/// Write a generated configuration to `<config_dir>/<base_name>.<ext>`.
+///
+/// # Arguments
+///
+/// * `project` - Generated configuration to write.
+/// * `task_properties` - Extra properties of generated tasks, by task name.
+/// * `config_dir` - Directory to write the file to.
+/// * `base_name` - Name of the file to write without an extension.
+/// * `format` - Format to write the file in.
+pub fn write_config(
+    project: &EvgProject,
+    task_properties: &HashMap<String, ExtraTaskProperties>,
+    config_dir: &Path,
+    base_name: &str,
+    format: OutputFormat,
+) -> Result<()> {
+    let filename = format!("{}.{}", base_name, format.extension());
+    std::fs::write(
+        config_dir.join(filename),
+        format.serialize(&project_value(project, task_properties)?)?,
+    )?;
+    Ok(())
+}
+
+/// Limits on the size of a single generated configuration file.
+#[derive(Debug, Clone, Default)]
+pub struct ShardLimits {
+    /// Maximum size of a file in bytes.
+    pub max_bytes: Option<usize>,
+    /// Maximum number of task definitions in a file.
+    pub max_tasks: Option<usize>,
+}
+
+impl ShardLimits {
+    /// Determine if any limits have been set.
+    pub fn is_limited(&self) -> bool {
+        self.max_bytes.is_some() || self.max_tasks.is_some()
+    }
+
+    fn exceeded_by(&self, n_bytes: usize, n_tasks: usize) -> bool {
+        self.max_bytes.map(|m| n_bytes > m).unwrap_or(false)
+            || self.max_tasks.map(|m| n_tasks > m).unwrap_or(false)
+    }
+}
+
+/// Manifest listing the files a generated configuration was split into.
+#[derive(Debug, Clone, Serialize)]
+pub struct ConfigManifest {
+    /// Configuration files in the order they should be used.
+    pub files: Vec<String>,
+}
+
+/// Bytes a separator between entries of a list may take, i.e. `,` in json. Yaml writes an empty
+/// list as `[]` on the line of its key, so a list that gains entries can also grow by a few bytes
+/// more than the entries themselves.
+const LIST_SEPARATOR_BYTES: usize = 4;
+
+/// A group of tasks that must be kept in the same file.
+///
+/// A display task is kept with all of its execution tasks, and a task group with all of the
+/// tasks in it.
+struct ConfigChunk<'a> {
+    build_variant: &'a BuildVariant,
+    task_refs: Vec<&'a TaskRef>,
+    display_task: Option<&'a DisplayTask>,
+    task_defs: Vec<&'a EvgTask>,
+    task_groups: Vec<&'a EvgTaskGroup>,
+}
+
+impl<'a> ConfigChunk<'a> {
+    /// Chunk of the same build variant without any tasks.
+    fn header(&self) -> ConfigChunk<'a> {
+        ConfigChunk {
+            build_variant: self.build_variant,
+            task_refs: vec![],
+            display_task: None,
+            task_defs: vec![],
+            task_groups: vec![],
+        }
+    }
+
+    /// Number of lists in a configuration this chunk adds entries to.
+    fn n_lists(&self) -> usize {
+        // The build variant entry and its task references.
+        2 + self.display_task.iter().count()
+            + usize::from(!self.task_defs.is_empty())
+            + usize::from(!self.task_groups.is_empty())
+    }
+}
+
+/// Names of the tasks a task reference runs, expanding references to task groups.
+fn referenced_tasks<'a>(
+    task_ref: &'a TaskRef,
+    task_group_map: &HashMap<&str, &'a EvgTaskGroup>,
+) -> Vec<&'a str> {
+    match task_group_map.get(task_ref.name.as_str()) {
+        Some(task_group) => task_group.tasks.iter().map(|t| t.as_str()).collect(),
+        None => vec![task_ref.name.as_str()],
+    }
+}
+
+fn new_chunk<'a>(
+    build_variant: &'a BuildVariant,
+    task_refs: Vec<&'a TaskRef>,
+    display_task: Option<&'a DisplayTask>,
+    task_def_map: &HashMap<String, &'a EvgTask>,
+    task_group_map: &HashMap<&str, &'a EvgTaskGroup>,
+) -> ConfigChunk<'a> {
+    let task_defs = task_refs
+        .iter()
+        .flat_map(|r| referenced_tasks(r, task_group_map))
+        .filter_map(|t| task_def_map.get(t).copied())
+        .collect();
+    let task_groups = task_refs
+        .iter()
+        .filter_map(|r| task_group_map.get(r.name.as_str()).copied())
+        .collect();
+    ConfigChunk {
+        build_variant,
+        task_refs,
+        display_task,
+        task_defs,
+        task_groups,
+    }
+}
+
+fn build_chunks(project: &EvgProject) -> Vec<ConfigChunk<'_>> {
+    let task_def_map = project.task_def_map();
+    let task_group_map: HashMap<&str, &EvgTaskGroup> = project
+        .task_groups
+        .iter()
+        .flatten()
+        .map(|tg| (tg.name.as_str(), tg))
+        .collect();
+    let mut chunks = vec![];
+    for build_variant in &project.buildvariants {
+        let mut ref_map: HashMap<&str, &TaskRef> = HashMap::new();
+        for task_ref in &build_variant.tasks {
+            for task in referenced_tasks(task_ref, &task_group_map) {
+                ref_map.insert(task, task_ref);
+            }
+        }
+        let mut grouped_refs = HashSet::new();
+
+        for display_task in build_variant.display_tasks.iter().flatten() {
+            let mut task_refs: Vec<&TaskRef> = vec![];
+            for task in &display_task.execution_tasks {
+                if let Some(task_ref) = ref_map.get(task.as_str()) {
+                    if !task_refs.iter().any(|r| r.name == task_ref.name) {
+                        task_refs.push(task_ref);
+                    }
+                }
+            }
+            grouped_refs.extend(task_refs.iter().map(|r| r.name.as_str()));
+            chunks.push(new_chunk(
+                build_variant,
+                task_refs,
+                Some(display_task),
+                &task_def_map,
+                &task_group_map,
+            ));
+        }
+
+        for task_ref in &build_variant.tasks {
+            if !grouped_refs.contains(task_ref.name.as_str()) {
+                chunks.push(new_chunk(
+                    build_variant,
+                    vec![task_ref],
+                    None,
+                    &task_def_map,
+                    &task_group_map,
+                ));
+            }
+        }
+    }
+
+    chunks
+}
+
+fn build_shard(chunks: &[ConfigChunk]) -> EvgProject {
+    let mut buildvariants: Vec<BuildVariant> = vec![];
+    let mut tasks: Vec<EvgTask> = vec![];
+    let mut task_groups: Vec<EvgTaskGroup> = vec![];
+    for chunk in chunks {
+        let index = buildvariants
+            .iter()
+            .position(|bv| bv.name == chunk.build_variant.name)
+            .unwrap_or_else(|| {
+                buildvariants.push(BuildVariant {
+                    tasks: vec![],
+                    display_tasks: Some(vec![]),
+                    ..chunk.build_variant.clone()
+                });
+                buildvariants.len() - 1
+            });
+        let build_variant = &mut buildvariants[index];
+        build_variant
+            .tasks
+            .extend(chunk.task_refs.iter().map(|t| (*t).clone()));
+        if let Some(display_task) = chunk.display_task {
+            if let Some(display_tasks) = build_variant.display_tasks.as_mut() {
+                display_tasks.push(display_task.clone());
+            }
+        }
+        for task_def in &chunk.task_defs {
+            if !tasks.iter().any(|t| t.name == task_def.name) {
+                tasks.push((*task_def).clone());
+            }
+        }
+        for task_group in &chunk.task_groups {
+            if !task_groups.iter().any(|t| t.name == task_group.name) {
+                task_groups.push((*task_group).clone());
+            }
+        }
+    }
+
+    EvgProject {
+        buildvariants,
+        tasks,
+        task_groups: Some(task_groups).filter(|tg| !tg.is_empty()),
+        ..Default::default()
+    }
+}
+
+/// Size of a configuration built from the given chunks, as it would be written.
+fn shard_size(
+    chunks: &[ConfigChunk],
+    task_properties: &HashMap<String, ExtraTaskProperties>,
+    format: OutputFormat,
+) -> Result<usize> {
+    Ok(format
+        .serialize(&project_value(&build_shard(chunks), task_properties)?)?
+        .len())
+}
+
+/// Split a generated configuration into several configurations that fit within the given limits.
+///
+/// Display tasks are always kept in the same configuration as their execution tasks. A group of
+/// tasks that exceeds the limits on its own is placed in a configuration by itself.
+///
+/// # Arguments
+///
+/// * `project` - Generated configuration to split.
+/// * `task_properties` - Extra properties of generated tasks, by task name.
+/// * `limits` - Limits each configuration should fit in.
+/// * `format` - Format the configurations will be written in, used to measure their size.
+pub fn shard_project(
+    project: &EvgProject,
+    task_properties: &HashMap<String, ExtraTaskProperties>,
+    limits: &ShardLimits,
+    format: OutputFormat,
+) -> Result<Vec<EvgProject>> {
+    let envelope_bytes = shard_size(&[], task_properties, format)?;
+    let mut header_bytes: HashMap<&str, usize> = HashMap::new();
+    let mut pending: VecDeque<(ConfigChunk, usize)> = VecDeque::new();
+    for chunk in build_chunks(project) {
+        // Each chunk is measured once, on its own, and shards are sized from these measurements.
+        let chunk_bytes = match limits.max_bytes {
+            Some(_) => {
+                if !header_bytes.contains_key(chunk.build_variant.name.as_str()) {
+                    header_bytes.insert(
+                        &chunk.build_variant.name,
+                        shard_size(&[chunk.header()], task_properties, format)?,
+                    );
+                }
+                shard_size(std::slice::from_ref(&chunk), task_properties, format)?
+                    + chunk.n_lists() * LIST_SEPARATOR_BYTES
+            }
+            None => 0,
+        };
+        pending.push_back((chunk, chunk_bytes));
+    }
+    let mut shards = vec![];
+
+    while !pending.is_empty() {
+        let mut current: Vec<ConfigChunk> = vec![];
+        let mut current_variants = HashSet::new();
+        let mut current_bytes = envelope_bytes;
+        let mut current_tasks = 0;
+        while let Some((chunk, chunk_bytes)) = pending.pop_front() {
+            // A chunk of a build variant not yet in the shard also adds the variant entry.
+            let added_bytes = if current_variants.contains(chunk.build_variant.name.as_str()) {
+                chunk_bytes.saturating_sub(
+                    header_bytes
+                        .get(chunk.build_variant.name.as_str())
+                        .copied()
+                        .unwrap_or(0),
+                )
+            } else {
+                chunk_bytes.saturating_sub(envelope_bytes)
+            };
+            let chunk_tasks = chunk.task_defs.len();
+            if !current.is_empty()
+                && limits.exceeded_by(current_bytes + added_bytes, current_tasks + chunk_tasks)
+            {
+                pending.push_front((chunk, chunk_bytes));
+                break;
+            }
+            current_bytes += added_bytes;
+            current_tasks += chunk_tasks;
+            current_variants.insert(chunk.build_variant.name.as_str());
+            current.push(chunk);
+        }
+
+        let shard = build_shard(&current);
+        // Chunk sizes allow for the separators between them, so a shard of several chunks fits.
+        if let Some(max_bytes) = limits.max_bytes.filter(|_| current.len() > 1) {
+            let shard_bytes = format
+                .serialize(&project_value(&shard, task_properties)?)?
+                .len();
+            if shard_bytes > max_bytes {
+                bail!(
+                    "Generated config of {} bytes exceeds the limit of {} bytes",
+                    shard_bytes,
+                    max_bytes
+                );
+            }
+        }
+        shards.push(shard);
+    }
+
+    if shards.is_empty() {
+        shards.push(build_shard(&[]));
+    }
+
+    Ok(shards)
+}
+
+/// Write a generated configuration split into files that fit within the given limits.
+///
+/// Files are named `<base_name>_<index>.<ext>` and a `<base_name>_manifest.<ext>` listing them
+/// in order is written alongside them.
+///
+/// # Arguments
+///
+/// * `project` - Generated configuration to write.
+/// * `task_properties` - Extra properties of generated tasks, by task name.
+/// * `config_dir` - Directory to write files to.
+/// * `base_name` - Base name of the files to write.
+/// * `limits` - Limits each file should fit in.
+/// * `format` - Format to write the files in.
+pub fn write_sharded_config(
+    project: &EvgProject,
+    task_properties: &HashMap<String, ExtraTaskProperties>,
+    config_dir: &Path,
+    base_name: &str,
+    limits: &ShardLimits,
+    format: OutputFormat,
+) -> Result<ConfigManifest> {
+    let shards = shard_project(project, task_properties, limits, format)?;
+    let mut files = vec![];
+    for (i, shard) in shards.iter().enumerate() {
+        let filename = format!("{}_{}.{}", base_name, i, format.extension());
+        std::fs::write(
+            config_dir.join(&filename),
+            format.serialize(&project_value(shard, task_properties)?)?,
+        )?;
+        files.push(filename);
+    }
+
+    let manifest = ConfigManifest { files };
+    std::fs::write(
+        config_dir.join(format!("{}_manifest.{}", base_name, format.extension())),
+        format.serialize(&manifest)?,
+    )?;
+
+    Ok(manifest)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maplit::hashmap;
+    use rstest::rstest;
+
+    fn build_project(variants: &[(&str, &[(&str, usize)])]) -> EvgProject {
+        let mut buildvariants = vec![];
+        let mut tasks = vec![];
+        for (bv_name, display_tasks) in variants {
+            let mut task_refs = vec![];
+            let mut dts = vec![];
+            for (dt_name, n_tasks) in display_tasks.iter() {
+                let names: Vec<String> = (0..*n_tasks)
+                    .map(|i| format!("{}_{}_{}", dt_name, i, bv_name))
+                    .collect();
+                for name in &names {
+                    let task = EvgTask {
+                        name: name.clone(),
+                        ..Default::default()
+                    };
+                    task_refs.push(task.get_reference(None, Some(false)));
+                    tasks.push(task);
+                }
+                dts.push(DisplayTask {
+                    name: dt_name.to_string(),
+                    execution_tasks: names,
+                });
+            }
+            buildvariants.push(BuildVariant {
+                name: bv_name.to_string(),
+                tasks: task_refs,
+                display_tasks: Some(dts),
+                ..Default::default()
+            });
+        }
+
+        EvgProject {
+            buildvariants,
+            tasks,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_unlimited_should_produce_a_single_shard() {
+        let project = build_project(&[("bv0", &[("t0", 3), ("t1", 2)]), ("bv1", &[("t0", 3)])]);
+
+        let shards = shard_project(
+            &project,
+            &HashMap::new(),
+            &ShardLimits::default(),
+            OutputFormat::Json,
+        )
+        .unwrap();
+
+        assert_eq!(shards.len(), 1);
+        assert_eq!(shards[0].tasks.len(), 8);
+        assert_eq!(shards[0].buildvariants.len(), 2);
+    }
+
+    #[test]
+    fn test_task_limit_should_keep_display_tasks_together() {
+        let project = build_project(&[("bv0", &[("t0", 3), ("t1", 2)]), ("bv1", &[("t0", 3)])]);
+        let limits = ShardLimits {
+            max_bytes: None,
+            max_tasks: Some(5),
+        };
+
+        let shards = shard_project(&project, &HashMap::new(), &limits, OutputFormat::Json).unwrap();
+
+        assert_eq!(shards.len(), 2);
+        assert_eq!(shards[0].tasks.len(), 5);
+        assert_eq!(shards[0].buildvariants.len(), 1);
+        assert_eq!(shards[1].tasks.len(), 3);
+        assert_eq!(shards[1].buildvariants[0].name, "bv1");
+        for shard in &shards {
+            for bv in &shard.buildvariants {
+                for dt in bv.display_tasks.as_ref().unwrap() {
+                    for task in &dt.execution_tasks {
+                        assert!(bv.tasks.iter().any(|t| &t.name == task));
+                        assert!(shard.tasks.iter().any(|t| &t.name == task));
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_byte_limit_should_place_oversized_groups_alone() {
+        let project = build_project(&[("bv0", &[("t0", 3), ("t1", 2)])]);
+        let limits = ShardLimits {
+            max_bytes: Some(1),
+            max_tasks: None,
+        };
+
+        let shards = shard_project(&project, &HashMap::new(), &limits, OutputFormat::Json).unwrap();
+
+        assert_eq!(shards.len(), 2);
+        assert_eq!(shards[0].tasks.len(), 3);
+        assert_eq!(shards[1].tasks.len(), 2);
+    }
+
+    #[test]
+    fn test_byte_limit_should_be_measured_in_output_format() {
+        let project = build_project(&[("bv0", &[("t0", 2), ("t1", 2), ("t2", 2)])]);
+        // Leave room for the separators the size estimate allows for.
+        let max_bytes = OutputFormat::Json.serialize(&project).unwrap().len() + 100;
+        let limits = ShardLimits {
+            max_bytes: Some(max_bytes),
+            max_tasks: None,
+        };
+
+        let json_shards =
+            shard_project(&project, &HashMap::new(), &limits, OutputFormat::Json).unwrap();
+        let pretty_shards =
+            shard_project(&project, &HashMap::new(), &limits, OutputFormat::JsonPretty).unwrap();
+
+        assert_eq!(json_shards.len(), 1);
+        assert!(pretty_shards.len() > 1);
+    }
+
+    #[rstest]
+    #[case(OutputFormat::Json)]
+    #[case(OutputFormat::JsonPretty)]
+    #[case(OutputFormat::Yaml)]
+    fn test_shards_should_fit_in_byte_limit(#[case] format: OutputFormat) {
+        let project = build_project(&[
+            ("bv0", &[("t0", 3), ("t1", 2), ("t2", 1)]),
+            ("bv1", &[("t0", 3), ("t1", 2)]),
+            ("bv2", &[("t0", 1), ("t1", 4)]),
+        ]);
+        let task_properties = HashMap::new();
+        let largest_chunk = build_chunks(&project)
+            .iter()
+            .map(|c| shard_size(std::slice::from_ref(c), &task_properties, format).unwrap())
+            .max()
+            .unwrap();
+        let max_bytes = largest_chunk * 2;
+        let limits = ShardLimits {
+            max_bytes: Some(max_bytes),
+            max_tasks: None,
+        };
+
+        let shards = shard_project(&project, &task_properties, &limits, format).unwrap();
+
+        assert!(shards.len() > 1);
+        assert_eq!(
+            shards.iter().map(|s| s.tasks.len()).sum::<usize>(),
+            project.tasks.len()
+        );
+        for shard in &shards {
+            assert!(format.serialize(shard).unwrap().len() <= max_bytes);
+        }
+    }
+
+    #[test]
+    fn test_task_groups_should_be_kept_with_their_tasks() {
+        let mut project = build_project(&[("bv0", &[("t0", 3), ("t1", 2)])]);
+        let group_tasks: Vec<String> = (0..3).map(|i| format!("t0_{}_bv0", i)).collect();
+        let build_variant = &mut project.buildvariants[0];
+        build_variant
+            .tasks
+            .retain(|t| !group_tasks.contains(&t.name));
+        build_variant.tasks.push(TaskRef {
+            name: "t0_bv0_TG".to_string(),
+            distros: None,
+            activate: Some(false),
+        });
+        project.task_groups = Some(vec![EvgTaskGroup {
+            name: "t0_bv0_TG".to_string(),
+            tasks: group_tasks,
+            ..Default::default()
+        }]);
+        let limits = ShardLimits {
+            max_bytes: None,
+            max_tasks: Some(3),
+        };
+
+        let shards = shard_project(&project, &HashMap::new(), &limits, OutputFormat::Json).unwrap();
+
+        assert_eq!(shards.len(), 2);
+        assert_eq!(shards[0].tasks.len(), 3);
+        assert_eq!(shards[0].buildvariants[0].tasks.len(), 1);
+        assert_eq!(shards[0].task_groups.as_ref().unwrap()[0].name, "t0_bv0_TG");
+        assert_eq!(shards[1].tasks.len(), 2);
+        assert!(shards[1].task_groups.is_none());
+    }
+
+    #[test]
+    fn test_output_format_from_str() {
+        assert_eq!(OutputFormat::from_str("json").unwrap(), OutputFormat::Json);
+        assert_eq!(
+            OutputFormat::from_str("json-pretty").unwrap(),
+            OutputFormat::JsonPretty
+        );
+        assert_eq!(OutputFormat::from_str("yaml").unwrap(), OutputFormat::Yaml);
+        assert!(OutputFormat::from_str("xml").is_err());
+    }
+
+    #[test]
+    fn test_all_output_formats_should_be_equivalent() {
+        let project = build_project(&[("bv0", &[("t0", 3), ("t1", 2)])]);
+        let expected = serde_json::to_value(&project).unwrap();
+
+        for format in &[
+            OutputFormat::Json,
+            OutputFormat::JsonPretty,
+            OutputFormat::Yaml,
+        ] {
+            let contents = format.serialize(&project).unwrap();
+            let value: serde_json::Value = serde_yaml::from_str(&contents).unwrap();
+
+            assert_eq!(value, expected);
+        }
+    }
+
+    #[test]
+    fn test_extra_task_properties_should_be_added_to_tasks() {
+        let project = build_project(&[("bv0", &[("t0", 2)])]);
+        let task_properties = hashmap! {
+            "t0_0_bv0".to_string() => ExtraTaskProperties {
+                priority: Some(50),
+                allowed_requesters: Some(vec!["patch".to_string()]),
+            },
+        };
+
+        let value = project_value(&project, &task_properties).unwrap();
+
+        let tasks = value["tasks"].as_array().unwrap();
+        let task = tasks.iter().find(|t| t["name"] == "t0_0_bv0").unwrap();
+        assert_eq!(task["priority"], 50);
+        assert_eq!(task["allowed_requesters"], serde_json::json!(["patch"]));
+        let other_task = tasks.iter().find(|t| t["name"] == "t0_1_bv0").unwrap();
+        assert!(other_task.get("priority").is_none());
+    }
+
+    #[test]
+    fn test_sorted_config_should_not_depend_on_input_order() {
+        let mut project = build_project(&[("bv1", &[("t1", 2), ("t0", 3)]), ("bv0", &[("t0", 1)])]);
+        let mut reversed = project.clone();
+        reversed.buildvariants.reverse();
+        reversed.tasks.reverse();
+        for bv in reversed.buildvariants.iter_mut() {
+            bv.tasks.reverse();
+            bv.display_tasks.as_mut().unwrap().reverse();
+        }
+
+        sort_config(&mut project);
+        sort_config(&mut reversed);
+
+        assert_eq!(
+            OutputFormat::Json.serialize(&project).unwrap(),
+            OutputFormat::Json.serialize(&reversed).unwrap()
+        );
+        assert_eq!(project.buildvariants[0].name, "bv0");
+        assert_eq!(project.tasks[0].name, "t0_0_bv0");
+    }
+}
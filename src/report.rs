@@ -0,0 +1,272 @@
+use std::fmt::Write as _;
+use std::path::Path;
+
+use anyhow::Result;
+use serde::Serialize;
+
+use crate::{
+    fingerprint::FINGERPRINT_NAME,
+    split_tasks::GeneratedSuite,
+    task_history::{get_test_name, TaskRuntimeHistory},
+    task_types::fuzzer_tasks::FuzzerTask,
+};
+
+/// Base name of the generation report files.
+pub const REPORT_NAME: &str = "generation_report";
+
+/// Base names of files written to the config dir that are not generated configuration.
+pub const GENERATION_ARTIFACTS: &[&str] = &[REPORT_NAME, FINGERPRINT_NAME];
+
+/// Time spent generating a task.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct TaskTimings {
+    /// Time spent discovering tests.
+    pub discovery_ms: u64,
+    /// Time spent looking up test history.
+    pub history_lookup_ms: u64,
+    /// Time spent splitting tests into sub-suites.
+    pub split_ms: u64,
+}
+
+/// Statistics about a generated sub-suite.
+#[derive(Debug, Clone, Serialize)]
+pub struct SubSuiteReport {
+    pub name: String,
+    pub num_tests: usize,
+    /// Runtime predicted from test history, in seconds.
+    pub predicted_runtime: f64,
+}
+
+/// Statistics about a generated task.
+#[derive(Debug, Clone, Serialize)]
+pub struct TaskReport {
+    pub build_variant: String,
+    pub task_name: String,
+    pub num_tests: usize,
+    pub num_sub_suites: usize,
+    pub sub_suites: Vec<SubSuiteReport>,
+    /// Ratio of the longest predicted sub-suite runtime to the mean, 1.0 is perfectly balanced.
+    pub imbalance_ratio: f64,
+    pub tests_without_history: Vec<String>,
+    pub timings: TaskTimings,
+}
+
+impl TaskReport {
+    /// Build a report for a generated resmoke task.
+    ///
+    /// # Arguments
+    ///
+    /// * `build_variant` - Build variant the task was generated for.
+    /// * `gen_suite` - Generated sub-suites of the task.
+    /// * `task_history` - Test history used to split the task.
+    /// * `timings` - Time spent generating the task.
+    pub fn from_generated_suite(
+        build_variant: &str,
+        gen_suite: &GeneratedSuite,
+        task_history: &TaskRuntimeHistory,
+        timings: TaskTimings,
+    ) -> Self {
+        let mut tests_without_history = vec![];
+        let sub_suites: Vec<SubSuiteReport> = gen_suite
+            .sub_suites
+            .iter()
+            .map(|s| {
+                let mut predicted_runtime = 0.0;
+                for test in &s.test_list {
+                    match task_history.test_map.get(&get_test_name(test)) {
+                        Some(test_history) => predicted_runtime += test_history.average_runtime,
+                        None => tests_without_history.push(test.clone()),
+                    }
+                }
+                SubSuiteReport {
+                    name: s.name.clone(),
+                    num_tests: s.test_list.len(),
+                    predicted_runtime,
+                }
+            })
+            .collect();
+
+        Self {
+            build_variant: build_variant.to_string(),
+            task_name: gen_suite.task_name.clone(),
+            num_tests: sub_suites.iter().map(|s| s.num_tests).sum(),
+            num_sub_suites: sub_suites.len(),
+            imbalance_ratio: imbalance_ratio(&sub_suites),
+            sub_suites,
+            tests_without_history,
+            timings,
+        }
+    }
+
+    /// Build a report for a generated fuzzer task.
+    ///
+    /// # Arguments
+    ///
+    /// * `build_variant` - Build variant the task was generated for.
+    /// * `fuzzer_task` - Generated fuzzer task.
+    pub fn from_fuzzer_task(build_variant: &str, fuzzer_task: &FuzzerTask) -> Self {
+        Self {
+            build_variant: build_variant.to_string(),
+            task_name: fuzzer_task.task_name.clone(),
+            num_tests: 0,
+            num_sub_suites: fuzzer_task.sub_tasks.len(),
+            sub_suites: vec![],
+            imbalance_ratio: 1.0,
+            tests_without_history: vec![],
+            timings: TaskTimings::default(),
+        }
+    }
+}
+
+fn imbalance_ratio(sub_suites: &[SubSuiteReport]) -> f64 {
+    if sub_suites.is_empty() {
+        return 1.0;
+    }
+    let max = sub_suites
+        .iter()
+        .map(|s| s.predicted_runtime)
+        .fold(0.0, f64::max);
+    let mean =
+        sub_suites.iter().map(|s| s.predicted_runtime).sum::<f64>() / sub_suites.len() as f64;
+    if mean > 0.0 {
+        max / mean
+    } else {
+        1.0
+    }
+}
+
+/// Report of all the tasks generated in a run.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GenerationReport {
+    pub tasks: Vec<TaskReport>,
+}
+
+impl GenerationReport {
+    pub fn add_task(&mut self, task_report: TaskReport) {
+        self.tasks.push(task_report);
+    }
+
+    /// Render the report as a Markdown table.
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# Generation Report\n");
+        let _ = writeln!(
+            out,
+            "| Build Variant | Task | Tests | Sub-suites | Max Runtime (s) | Imbalance | No History | Discovery (ms) | History (ms) | Split (ms) |"
+        );
+        let _ = writeln!(out, "|---|---|---|---|---|---|---|---|---|---|");
+        for task in &self.tasks {
+            let max_runtime = task
+                .sub_suites
+                .iter()
+                .map(|s| s.predicted_runtime)
+                .fold(0.0, f64::max);
+            let _ = writeln!(
+                out,
+                "| {} | {} | {} | {} | {:.1} | {:.2} | {} | {} | {} | {} |",
+                task.build_variant,
+                task.task_name,
+                task.num_tests,
+                task.num_sub_suites,
+                max_runtime,
+                task.imbalance_ratio,
+                task.tests_without_history.len(),
+                task.timings.discovery_ms,
+                task.timings.history_lookup_ms,
+                task.timings.split_ms,
+            );
+        }
+        out
+    }
+
+    /// Write the report as json, and optionally Markdown, to the given directory.
+    ///
+    /// Tasks are sorted by build variant and task name so the report is stable.
+    ///
+    /// # Arguments
+    ///
+    /// * `config_dir` - Directory to write the report to.
+    /// * `include_markdown` - Also write a Markdown version of the report.
+    pub fn write(&mut self, config_dir: &Path, include_markdown: bool) -> Result<()> {
+        self.tasks.sort_by(|a, b| {
+            (&a.build_variant, &a.task_name).cmp(&(&b.build_variant, &b.task_name))
+        });
+        std::fs::write(
+            config_dir.join(format!("{}.json", REPORT_NAME)),
+            serde_json::to_string_pretty(self)?,
+        )?;
+        if include_markdown {
+            std::fs::write(
+                config_dir.join(format!("{}.md", REPORT_NAME)),
+                self.to_markdown(),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::split_tasks::SubSuite;
+    use crate::task_history::TestRuntimeHistory;
+    use std::collections::HashMap;
+
+    fn build_history(tests: &[(&str, f64)]) -> TaskRuntimeHistory {
+        let test_map: HashMap<String, TestRuntimeHistory> = tests
+            .iter()
+            .map(|(name, runtime)| {
+                (
+                    get_test_name(name),
+                    TestRuntimeHistory {
+                        test_name: name.to_string(),
+                        average_runtime: *runtime,
+                        hooks: vec![],
+                    },
+                )
+            })
+            .collect();
+        TaskRuntimeHistory {
+            suite_name: "suite".to_string(),
+            task_name: "task".to_string(),
+            test_map,
+        }
+    }
+
+    #[test]
+    fn test_report_from_generated_suite() {
+        let gen_suite = GeneratedSuite {
+            task_name: "task".to_string(),
+            suite_name: "suite".to_string(),
+            build_variant: "bv".to_string(),
+            sub_suites: vec![
+                SubSuite {
+                    name: "task_0_bv".to_string(),
+                    test_list: vec!["jstests/t0.js".to_string(), "jstests/t1.js".to_string()],
+                },
+                SubSuite {
+                    name: "task_1_bv".to_string(),
+                    test_list: vec!["jstests/t2.js".to_string(), "jstests/new.js".to_string()],
+                },
+            ],
+            discovery_duration_ms: 0,
+            fingerprint: None,
+            suite_config: None,
+        };
+        let history = build_history(&[
+            ("jstests/t0.js", 10.0),
+            ("jstests/t1.js", 20.0),
+            ("jstests/t2.js", 10.0),
+        ]);
+
+        let report =
+            TaskReport::from_generated_suite("bv", &gen_suite, &history, TaskTimings::default());
+
+        assert_eq!(report.num_tests, 4);
+        assert_eq!(report.num_sub_suites, 2);
+        assert_eq!(report.sub_suites[0].predicted_runtime, 30.0);
+        assert_eq!(report.sub_suites[1].predicted_runtime, 10.0);
+        assert_eq!(report.imbalance_ratio, 1.5);
+        assert_eq!(report.tests_without_history, vec!["jstests/new.js"]);
+    }
+}
@@ -1,6 +1,6 @@
 use std::{path::Path, str::FromStr, time::Instant};
 
-use anyhow::{bail, Result};
+use anyhow::{bail, Context, Result};
 use cmd_lib::run_fun;
 use serde::Deserialize;
 use tracing::{event, Level};
@@ -48,34 +48,232 @@ pub struct MultiversionConfig {
 }
 
 impl MultiversionConfig {
-    pub fn from_resmoke() -> MultiversionConfig {
+    pub fn from_resmoke() -> Result<MultiversionConfig> {
         let cmd_output = run_fun!(
             python buildscripts/resmoke.py multiversion-config
-        )
-        .unwrap();
-        serde_yaml::from_str(&cmd_output).unwrap()
+        )?;
+        Ok(serde_yaml::from_str(&cmd_output)?)
+    }
+
+    /// Read the multiversion configuration from a yaml file.
+    pub fn from_yaml_file(path: &Path) -> Result<MultiversionConfig> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(serde_yaml::from_str(&contents)?)
+    }
+
+    /// Load the multiversion configuration from the first available source.
+    ///
+    /// Explicitly provided versions are used first, then the config file, and finally
+    /// resmoke is queried.
+    ///
+    /// # Arguments
+    ///
+    /// * `last_versions` - Versions provided on the command line.
+    /// * `config_file` - Location of a multiversion config file.
+    pub fn load(
+        last_versions: &[String],
+        config_file: Option<&Path>,
+    ) -> Result<MultiversionConfig> {
+        if !last_versions.is_empty() {
+            Ok(MultiversionConfig {
+                last_versions: last_versions.to_vec(),
+            })
+        } else if let Some(config_file) = config_file {
+            Self::from_yaml_file(config_file)
+        } else {
+            Self::from_resmoke()
+        }
     }
 }
 
 #[derive(Debug, PartialEq, Clone)]
 pub enum SuiteFixtureType {
     Shell,
-    Repl,
-    Shard,
+    Standalone,
+    Repl {
+        num_nodes: Option<u64>,
+    },
+    MultiRepl {
+        num_replica_sets: Option<u64>,
+        num_nodes_per_replica_set: Option<u64>,
+    },
+    Shard {
+        num_shards: Option<u64>,
+        num_rs_nodes_per_shard: Option<u64>,
+    },
+    TalkDirectlyToShards {
+        num_shards: Option<u64>,
+        num_rs_nodes_per_shard: Option<u64>,
+    },
     Other,
 }
 
 impl SuiteFixtureType {
-    pub fn get_version_combinations(&self) -> Vec<String> {
+    /// Build a fixture type from the `fixture` section of a resmoke suite's executor.
+    ///
+    /// # Arguments
+    ///
+    /// * `fixture_class` - Resmoke class of the fixture.
+    /// * `fixture` - Fixture configuration to read node and shard counts from.
+    fn from_fixture_class(fixture_class: &str, fixture: &Hash) -> Self {
+        let count = |key: &str| {
+            fixture
+                .get(&Yaml::from_str(key))
+                .and_then(|v| v.as_i64())
+                .map(|v| v as u64)
+        };
+        match fixture_class {
+            "MongoDFixture" => Self::Standalone,
+            "ReplicaSetFixture" => Self::Repl {
+                num_nodes: count("num_nodes"),
+            },
+            "MultiReplicaSetFixture" => Self::MultiRepl {
+                num_replica_sets: count("num_replica_sets"),
+                num_nodes_per_replica_set: count("num_nodes_per_replica_set"),
+            },
+            "ShardedClusterFixture" => Self::Shard {
+                num_shards: count("num_shards"),
+                num_rs_nodes_per_shard: count("num_rs_nodes_per_shard"),
+            },
+            "TalkDirectlyToShardsFixture" => Self::TalkDirectlyToShards {
+                num_shards: count("num_shards"),
+                num_rs_nodes_per_shard: count("num_rs_nodes_per_shard"),
+            },
+            _ => Self::Other,
+        }
+    }
+
+    /// Name of the resmoke fixture class this fixture type was read from.
+    pub fn fixture_class(&self) -> Option<&str> {
         match self {
-            Self::Shard => vec!["new_old_old_new".to_string()],
-            Self::Repl => ["new_new_old", "new_old_new", "old_new_new"]
-                .iter()
-                .map(|v| v.to_string())
-                .collect(),
-            _ => vec!["".to_string()],
+            Self::Standalone => Some("MongoDFixture"),
+            Self::Repl { .. } => Some("ReplicaSetFixture"),
+            Self::MultiRepl { .. } => Some("MultiReplicaSetFixture"),
+            Self::Shard { .. } => Some("ShardedClusterFixture"),
+            Self::TalkDirectlyToShards { .. } => Some("TalkDirectlyToShardsFixture"),
+            Self::Shell | Self::Other => None,
         }
     }
+
+    /// Get the mixed version combinations this fixture should be run with.
+    ///
+    /// # Arguments
+    ///
+    /// * `config` - Configuration describing version combinations per fixture.
+    pub fn get_version_combinations(&self, config: &VersionCombinationConfig) -> Vec<String> {
+        config
+            .fixtures
+            .iter()
+            .find(|f| f.matches(self))
+            .map(|f| f.combinations.clone())
+            .unwrap_or_else(|| vec!["".to_string()])
+    }
+}
+
+/// Mixed version combinations to use for fixtures matching the given criteria.
+#[derive(Debug, Clone, Deserialize)]
+pub struct FixtureVersionCombinations {
+    /// Resmoke fixture class to match.
+    pub class: String,
+    /// Number of replica set nodes to match, any if not specified.
+    pub num_nodes: Option<u64>,
+    /// Number of replica sets to match, any if not specified.
+    pub num_replica_sets: Option<u64>,
+    /// Number of nodes per replica set to match, any if not specified.
+    pub num_nodes_per_replica_set: Option<u64>,
+    /// Number of shards to match, any if not specified.
+    pub num_shards: Option<u64>,
+    /// Number of replica set nodes per shard to match, any if not specified.
+    pub num_rs_nodes_per_shard: Option<u64>,
+    /// Version combinations to generate for matching fixtures.
+    pub combinations: Vec<String>,
+}
+
+impl FixtureVersionCombinations {
+    fn new(class: &str, combinations: &[&str]) -> Self {
+        Self {
+            class: class.to_string(),
+            num_nodes: None,
+            num_replica_sets: None,
+            num_nodes_per_replica_set: None,
+            num_shards: None,
+            num_rs_nodes_per_shard: None,
+            combinations: combinations.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+
+    /// Determine if the given fixture type matches this entry.
+    pub fn matches(&self, fixture_type: &SuiteFixtureType) -> bool {
+        if fixture_type.fixture_class() != Some(self.class.as_str()) {
+            return false;
+        }
+
+        match fixture_type {
+            SuiteFixtureType::Repl { num_nodes } => count_matches(self.num_nodes, *num_nodes),
+            SuiteFixtureType::MultiRepl {
+                num_replica_sets,
+                num_nodes_per_replica_set,
+            } => {
+                count_matches(self.num_replica_sets, *num_replica_sets)
+                    && count_matches(self.num_nodes_per_replica_set, *num_nodes_per_replica_set)
+            }
+            SuiteFixtureType::Shard {
+                num_shards,
+                num_rs_nodes_per_shard,
+            }
+            | SuiteFixtureType::TalkDirectlyToShards {
+                num_shards,
+                num_rs_nodes_per_shard,
+            } => {
+                count_matches(self.num_shards, *num_shards)
+                    && count_matches(self.num_rs_nodes_per_shard, *num_rs_nodes_per_shard)
+            }
+            _ => true,
+        }
+    }
+}
+
+fn count_matches(expected: Option<u64>, actual: Option<u64>) -> bool {
+    expected.is_none() || expected == actual
+}
+
+/// Configuration of the mixed version combinations to generate for each fixture type.
+///
+/// Entries are checked in order and the first matching entry is used.
+#[derive(Debug, Clone, Deserialize)]
+pub struct VersionCombinationConfig {
+    pub fixtures: Vec<FixtureVersionCombinations>,
+}
+
+impl Default for VersionCombinationConfig {
+    fn default() -> Self {
+        Self {
+            fixtures: vec![
+                FixtureVersionCombinations::new("ShardedClusterFixture", &["new_old_old_new"]),
+                FixtureVersionCombinations::new(
+                    "ReplicaSetFixture",
+                    &["new_new_old", "new_old_new", "old_new_new"],
+                ),
+            ],
+        }
+    }
+}
+
+impl VersionCombinationConfig {
+    /// Read the version combination configuration from a yaml file.
+    pub fn from_yaml_file(path: &Path) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)?;
+        Ok(serde_yaml::from_str(&contents)?)
+    }
+
+    /// Check if any version combinations are configured for the given fixture.
+    ///
+    /// # Arguments
+    ///
+    /// * `fixture_type` - Fixture to check.
+    pub fn has_combinations(&self, fixture_type: &SuiteFixtureType) -> bool {
+        self.fixtures.iter().any(|f| f.matches(fixture_type))
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -96,11 +294,27 @@ impl FromStr for ResmokeSuiteConfig {
 
 impl ResmokeSuiteConfig {
     pub fn read_suite_config(suite_name: &str) -> Self {
+        Self::try_read_suite_config(suite_name).unwrap()
+    }
+
+    /// Read the configuration of a suite from resmoke.
+    ///
+    /// # Arguments
+    ///
+    /// * `suite_name` - Name of suite to read.
+    pub fn try_read_suite_config(suite_name: &str) -> Result<Self> {
         let cmd_output = run_fun!(
             python buildscripts/resmoke.py suiteconfig --suite $suite_name
-        )
-        .unwrap();
-        Self::from_str(&cmd_output).unwrap()
+        )?;
+        match YamlLoader::load_from_str(&cmd_output)?.into_iter().next() {
+            Some(config) => Ok(Self { config }),
+            None => bail!("Suite '{}' has no configuration", suite_name),
+        }
+    }
+
+    /// Get the configuration as YAML.
+    pub fn to_yaml_string(&self) -> String {
+        dump_yaml(&self.config)
     }
 
     pub fn get_fixture_type(&self) -> Result<SuiteFixtureType> {
@@ -113,13 +327,10 @@ impl ResmokeSuiteConfig {
                             if let Some(fixture_class) = fixture.get(&Yaml::from_str("class")) {
                                 match fixture_class {
                                     Yaml::String(fixture_class) => {
-                                        if fixture_class == "ShardedClusterFixture" {
-                                            Ok(SuiteFixtureType::Shard)
-                                        } else if fixture_class == "ReplicaSetFixture" {
-                                            Ok(SuiteFixtureType::Repl)
-                                        } else {
-                                            Ok(SuiteFixtureType::Other)
-                                        }
+                                        Ok(SuiteFixtureType::from_fixture_class(
+                                            fixture_class,
+                                            fixture,
+                                        ))
                                     }
                                     _ => Ok(SuiteFixtureType::Other),
                                 }
@@ -137,9 +348,22 @@ impl ResmokeSuiteConfig {
         }
     }
 
+    /// Get the tests listed in the `roots` of the suite's selector.
+    pub fn get_test_roots(&self) -> Vec<String> {
+        match &self.config["selector"]["roots"] {
+            Yaml::Array(roots) => roots
+                .iter()
+                .filter_map(|r| r.as_str().map(|r| r.to_string()))
+                .collect(),
+            _ => vec![],
+        }
+    }
+
     fn get_executor(&self) -> Result<&Yaml> {
         match &self.config {
-            Yaml::Hash(map) => Ok(map.get(&Yaml::from_str("executor")).unwrap()),
+            Yaml::Hash(map) => map
+                .get(&Yaml::from_str("executor"))
+                .context("suite has no executor"),
             _ => bail!("Expected map at root of resmoke config"),
         }
     }
@@ -197,14 +421,70 @@ impl ResmokeSuiteConfig {
             }
         }
 
-        let mut out_str = String::new();
-        let mut emitter = YamlEmitter::new(&mut out_str);
-        emitter.dump(&Yaml::Hash(new_map)).unwrap();
+        dump_yaml(&Yaml::Hash(new_map))
+    }
+
+    /// Create the configuration for running this suite against a mix of binary versions.
+    ///
+    /// # Arguments
+    ///
+    /// * `old_bin_version` - Version of old binaries to use (i.e. `last_lts`).
+    /// * `mixed_bin_versions` - Combination of versions for the fixture nodes (i.e. `new_old_new`).
+    pub fn update_multiversion_config(
+        &self,
+        old_bin_version: &str,
+        mixed_bin_versions: &str,
+    ) -> Result<String> {
+        let mut config = self.config.clone();
+        let fixture = match &mut config {
+            Yaml::Hash(root) => match root.get_mut(&Yaml::from_str("executor")) {
+                Some(Yaml::Hash(executor)) => match executor.get_mut(&Yaml::from_str("fixture")) {
+                    Some(Yaml::Hash(fixture)) => fixture,
+                    _ => bail!("suite has no fixture to run against multiple versions"),
+                },
+                _ => bail!("suite has no executor"),
+            },
+            _ => bail!("Expected map at root of resmoke config"),
+        };
+        fixture.insert(
+            Yaml::from_str("old_bin_version"),
+            Yaml::String(old_bin_version.to_string()),
+        );
+        if !mixed_bin_versions.is_empty() {
+            fixture.insert(
+                Yaml::from_str("mixed_bin_versions"),
+                Yaml::Array(
+                    mixed_bin_versions
+                        .split('_')
+                        .map(|v| Yaml::String(v.to_string()))
+                        .collect(),
+                ),
+            );
+        }
 
-        out_str
+        Ok(dump_yaml(&config))
     }
 }
 
+/// A resmoke suite to run against a mix of binary versions.
+#[derive(Debug, Clone)]
+pub struct MultiversionSuite {
+    /// Name of the generated suite.
+    pub name: String,
+    /// Version of old binaries to use (i.e. `last_lts`).
+    pub old_bin_version: String,
+    /// Combination of versions for the fixture nodes (i.e. `new_old_new`).
+    pub mixed_bin_versions: String,
+}
+
+fn dump_yaml(yaml: &Yaml) -> String {
+    let mut out_str = String::new();
+    let mut emitter = YamlEmitter::new(&mut out_str);
+    emitter.dump(yaml).unwrap();
+
+    out_str
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -235,6 +515,22 @@ mod tests {
         assert_eq!(config.get_fixture_type().unwrap(), SuiteFixtureType::Shell);
     }
 
+    #[test]
+    fn test_missing_executor_should_be_an_error() {
+        let config_yaml = "
+            test_kind: js_test
+
+            selector:
+              roots:
+                - jstests/auth/*.js
+        ";
+
+        let config = ResmokeSuiteConfig::from_str(config_yaml).unwrap();
+
+        let error = config.get_fixture_type().unwrap_err();
+        assert!(format!("{:#}", error).contains("suite has no executor"));
+    }
+
     #[test]
     fn test_shared_cluster_fixture_should_return_sharded() {
         let config_yaml = "
@@ -260,7 +556,13 @@ mod tests {
 
         let config = ResmokeSuiteConfig::from_str(config_yaml).unwrap();
 
-        assert_eq!(config.get_fixture_type().unwrap(), SuiteFixtureType::Shard);
+        assert_eq!(
+            config.get_fixture_type().unwrap(),
+            SuiteFixtureType::Shard {
+                num_shards: Some(2),
+                num_rs_nodes_per_shard: None,
+            }
+        );
     }
 
     #[test]
@@ -288,7 +590,10 @@ mod tests {
 
         let config = ResmokeSuiteConfig::from_str(config_yaml).unwrap();
 
-        assert_eq!(config.get_fixture_type().unwrap(), SuiteFixtureType::Repl);
+        assert_eq!(
+            config.get_fixture_type().unwrap(),
+            SuiteFixtureType::Repl { num_nodes: Some(3) }
+        );
     }
 
     #[test]
@@ -317,4 +622,217 @@ mod tests {
 
         assert_eq!(config.get_fixture_type().unwrap(), SuiteFixtureType::Other);
     }
+
+    #[test]
+    fn test_multi_replica_set_fixture_should_return_multi_repl() {
+        let config_yaml = "
+            test_kind: js_test
+
+            selector:
+              roots:
+                - jstests/auth/*.js
+
+            executor:
+              fixture:
+                class: MultiReplicaSetFixture
+                num_replica_sets: 2
+                num_nodes_per_replica_set: 3
+        ";
+
+        let config = ResmokeSuiteConfig::from_str(config_yaml).unwrap();
+
+        assert_eq!(
+            config.get_fixture_type().unwrap(),
+            SuiteFixtureType::MultiRepl {
+                num_replica_sets: Some(2),
+                num_nodes_per_replica_set: Some(3),
+            }
+        );
+    }
+
+    #[test]
+    fn test_mongod_fixture_should_return_standalone() {
+        let config_yaml = "
+            test_kind: js_test
+
+            selector:
+              roots:
+                - jstests/auth/*.js
+
+            executor:
+              fixture:
+                class: MongoDFixture
+        ";
+
+        let config = ResmokeSuiteConfig::from_str(config_yaml).unwrap();
+
+        assert_eq!(
+            config.get_fixture_type().unwrap(),
+            SuiteFixtureType::Standalone
+        );
+    }
+
+    // get_version_combinations tests.
+    #[test]
+    fn test_default_version_combinations() {
+        let config = VersionCombinationConfig::default();
+        let shard = SuiteFixtureType::Shard {
+            num_shards: Some(2),
+            num_rs_nodes_per_shard: Some(2),
+        };
+        let repl = SuiteFixtureType::Repl { num_nodes: Some(3) };
+
+        assert_eq!(
+            shard.get_version_combinations(&config),
+            vec!["new_old_old_new"]
+        );
+        assert_eq!(
+            repl.get_version_combinations(&config),
+            vec!["new_new_old", "new_old_new", "old_new_new"]
+        );
+        assert_eq!(
+            SuiteFixtureType::Shell.get_version_combinations(&config),
+            vec![""]
+        );
+    }
+
+    #[test]
+    fn test_version_combinations_should_use_first_matching_entry() {
+        let config: VersionCombinationConfig = serde_yaml::from_str(
+            "
+            fixtures:
+              - class: ReplicaSetFixture
+                num_nodes: 2
+                combinations: [new_old, old_new]
+              - class: ReplicaSetFixture
+                combinations: [new_new_old]
+            ",
+        )
+        .unwrap();
+
+        assert_eq!(
+            SuiteFixtureType::Repl { num_nodes: Some(2) }.get_version_combinations(&config),
+            vec!["new_old", "old_new"]
+        );
+        assert_eq!(
+            SuiteFixtureType::Repl { num_nodes: Some(3) }.get_version_combinations(&config),
+            vec!["new_new_old"]
+        );
+    }
+
+    // update_multiversion_config tests.
+    #[test]
+    fn test_update_multiversion_config_should_set_fixture_versions() {
+        let config_yaml = "
+            test_kind: js_test
+
+            selector:
+              roots:
+                - jstests/auth/*.js
+
+            executor:
+              fixture:
+                class: ReplicaSetFixture
+                num_nodes: 3
+        ";
+        let config = ResmokeSuiteConfig::from_str(config_yaml).unwrap();
+
+        let mv_config = config
+            .update_multiversion_config("last_lts", "new_old_new")
+            .unwrap();
+
+        let mv_yaml = &YamlLoader::load_from_str(&mv_config).unwrap()[0];
+        let fixture = &mv_yaml["executor"]["fixture"];
+        assert_eq!(fixture["old_bin_version"].as_str(), Some("last_lts"));
+        assert_eq!(
+            fixture["mixed_bin_versions"],
+            Yaml::Array(vec![
+                Yaml::from_str("new"),
+                Yaml::from_str("old"),
+                Yaml::from_str("new"),
+            ])
+        );
+        assert_eq!(fixture["num_nodes"].as_i64(), Some(3));
+    }
+
+    #[test]
+    fn test_update_multiversion_config_without_mixed_versions() {
+        let config_yaml = "
+            test_kind: js_test
+
+            executor:
+              fixture:
+                class: MongoDFixture
+        ";
+        let config = ResmokeSuiteConfig::from_str(config_yaml).unwrap();
+
+        let mv_config = config
+            .update_multiversion_config("last_continuous", "")
+            .unwrap();
+
+        let mv_yaml = &YamlLoader::load_from_str(&mv_config).unwrap()[0];
+        let fixture = &mv_yaml["executor"]["fixture"];
+        assert_eq!(fixture["old_bin_version"].as_str(), Some("last_continuous"));
+        assert!(fixture["mixed_bin_versions"].is_badvalue());
+    }
+
+    #[test]
+    fn test_update_multiversion_config_without_fixture_should_be_an_error() {
+        let config_yaml = "
+            test_kind: js_test
+
+            executor:
+              config:
+                shell_options:
+                  nodb: ''
+        ";
+        let config = ResmokeSuiteConfig::from_str(config_yaml).unwrap();
+
+        let error = config
+            .update_multiversion_config("last_lts", "")
+            .unwrap_err();
+
+        assert!(error.to_string().contains("no fixture"));
+    }
+
+    // MultiversionConfig::load tests.
+    fn write_multiversion_config() -> tempfile::NamedTempFile {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        std::fs::write(
+            file.path(),
+            "last_versions:\n  - last_lts\n  - last_continuous\n",
+        )
+        .unwrap();
+        file
+    }
+
+    #[test]
+    fn test_multiversion_config_should_be_read_from_file() {
+        let file = write_multiversion_config();
+
+        let config = MultiversionConfig::load(&[], Some(file.path())).unwrap();
+
+        assert_eq!(
+            config.last_versions,
+            vec!["last_lts".to_string(), "last_continuous".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_last_versions_should_take_precedence_over_file() {
+        let file = write_multiversion_config();
+
+        let config =
+            MultiversionConfig::load(&["last_lts".to_string()], Some(file.path())).unwrap();
+
+        assert_eq!(config.last_versions, vec!["last_lts".to_string()]);
+    }
+
+    #[test]
+    fn test_missing_multiversion_config_file_should_be_an_error() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let path = tmp_dir.path().join("multiversion.yml");
+
+        assert!(MultiversionConfig::load(&[], Some(&path)).is_err());
+    }
 }
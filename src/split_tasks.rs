@@ -1,15 +1,59 @@
-use crate::resmoke::TestDiscovery;
+use crate::expansions::Expansions;
+use crate::fingerprint::{PreviousGeneration, TaskFingerprint};
+use crate::generator_config::GeneratorConfig;
+use crate::resmoke::{ResmokeSuiteConfig, TestDiscovery};
 use crate::task_history::{get_test_name, TaskRuntimeHistory};
+use crate::task_properties::{set_compile_variant, InheritedTaskProperties};
+use crate::util::name_generated_task;
 use maplit::hashmap;
+use serde::{Deserialize, Serialize};
 use shrub_rs::models::commands::{fn_call, fn_call_with_params, EvgCommand};
 use shrub_rs::models::params::ParamValue;
 use shrub_rs::models::task::{EvgTask, TaskDependency, TaskRef};
-use shrub_rs::models::variant::DisplayTask;
-use tracing::{event, Level};
+use shrub_rs::models::task_group::EvgTaskGroup;
+use shrub_rs::models::variant::{BuildVariant, DisplayTask};
 use std::cmp::min;
 use std::collections::HashMap;
 use std::path::Path;
 use std::sync::Arc;
+use std::time::Instant;
+use tracing::{event, Level};
+
+const TASK_GROUP_SUFFIX: &str = "TG";
+
+/// Expansion naming a group of build variants that share test history and test lists.
+///
+/// Tasks are split once per group, and every build variant in the group reuses that split
+/// with its own sub-suite names. Build variants without it form a group of their own.
+pub const SPLIT_FAMILY_EXPANSION: &str = "split_family";
+
+/// Get the split family of a build variant.
+///
+/// # Arguments
+///
+/// * `build_variant` - Build variant tasks are split for.
+/// * `expansions` - Expansions available to every build variant.
+pub fn split_family(build_variant: &BuildVariant, expansions: &Expansions) -> String {
+    expansions
+        .for_build_variant(build_variant)
+        .get(SPLIT_FAMILY_EXPANSION)
+        .unwrap_or(&build_variant.name)
+        .to_string()
+}
+
+fn sub_suite_name(task_name: &str, index: usize, bv_name: &str) -> String {
+    format!("{}_{}_{}", task_name, index, bv_name)
+}
+
+/// Get the name of the suite running the tests of a task that are in none of its sub-suites.
+///
+/// # Arguments
+///
+/// * `task_name` - Name of the task that was split.
+/// * `bv_name` - Build variant the task was split for.
+pub fn misc_suite_name(task_name: &str, bv_name: &str) -> String {
+    name_generated_task(task_name, None, None, Some(bv_name))
+}
 
 /// Parameters describing how a specific resmoke suite should be generated.
 #[derive(Clone, Debug)]
@@ -22,47 +66,133 @@ pub struct ResmokeGenParams {
     pub resmoke_args: String,
     pub resmoke_jobs_max: Option<u64>,
     pub config_location: Option<String>,
+    pub exec_timeout_secs: Option<u64>,
+    pub inherited_properties: InheritedTaskProperties,
+    pub depends_on: Option<Vec<TaskDependency>>,
+    pub compile_variant: Option<String>,
+    pub use_task_group: bool,
+    pub task_group_max_hosts: Option<u64>,
+    pub activate: bool,
 }
 
-#[derive(Debug, Clone)]
+impl ResmokeGenParams {
+    /// Distros sub-tasks should run on, if not the build variant default.
+    pub fn distros(&self) -> Option<Vec<String>> {
+        if self.use_large_distro {
+            self.large_distro_name.clone().map(|d| vec![d])
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct SubSuite {
     pub name: String,
     pub test_list: Vec<String>,
 }
 
 impl SubSuite {
-    pub fn task_ref(&self) -> TaskRef {
+    pub fn task_ref(&self, gen_params: &ResmokeGenParams) -> TaskRef {
         TaskRef {
             name: self.name.to_string(),
-            distros: None,
-            activate: Some(false),
+            distros: gen_params.distros(),
+            activate: Some(gen_params.activate),
         }
     }
 
     pub fn task(&self, gen_params: &ResmokeGenParams) -> EvgTask {
-        EvgTask {
+        let run_tests =
+            fn_call_with_params("run generated tests", run_test_vars(&self.name, gen_params));
+        let commands = if gen_params.use_task_group {
+            vec![run_tests]
+        } else {
+            let mut commands = setup_commands(gen_params.require_multiversion_setup);
+            commands.push(run_tests);
+            commands
+        };
+        let mut task = EvgTask {
             name: self.name.clone(),
-            commands: resmoke_commands(
-                "run generated tests",
-                run_test_vars(&self.name, gen_params),
-                gen_params.require_multiversion_setup,
-            ),
-            depends_on: Some(dependencies()),
+            commands,
+            depends_on: gen_params.depends_on.clone(),
+            exec_timeout_secs: gen_params.exec_timeout_secs,
             ..Default::default()
-        }
+        };
+        gen_params.inherited_properties.apply(&mut task);
+        task.depends_on.get_or_insert_with(dependencies);
+        set_compile_variant(&mut task, gen_params.compile_variant.as_deref());
+        task
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct GeneratedSuite {
     pub task_name: String,
     pub suite_name: String,
+    /// Build variant the suite was split for.
+    pub build_variant: String,
     pub sub_suites: Vec<SubSuite>,
+    /// Time spent discovering the tests in the suite.
+    ///
+    /// Not serialized, so a split written for reuse is the same on every run.
+    #[serde(skip)]
+    pub discovery_duration_ms: u64,
+    /// Fingerprint of the inputs the suite was split from.
+    pub fingerprint: Option<TaskFingerprint>,
+    /// Resmoke configuration of the suite, read once while splitting.
+    ///
+    /// Not serialized, a split read back from a previous generation has its suite files copied.
+    #[serde(skip)]
+    pub suite_config: Option<ResmokeSuiteConfig>,
 }
 
 impl GeneratedSuite {
-    pub fn task_refs(&self) -> Vec<TaskRef> {
-        self.sub_suites.iter().map(|s| s.task_ref()).collect()
+    pub fn task_refs(&self, gen_params: &ResmokeGenParams) -> Vec<TaskRef> {
+        if gen_params.use_task_group {
+            return vec![TaskRef {
+                name: self.task_group_name(),
+                distros: gen_params.distros(),
+                activate: Some(gen_params.activate),
+            }];
+        }
+        self.sub_suites
+            .iter()
+            .map(|s| s.task_ref(gen_params))
+            .collect()
+    }
+
+    fn task_group_name(&self) -> String {
+        format!(
+            "{}_{}_{}",
+            self.task_name, self.build_variant, TASK_GROUP_SUFFIX
+        )
+    }
+
+    /// Task group running the sub-suites with shared setup, if tasks should be grouped.
+    ///
+    /// The group may run every sub-suite at once unless a lower maximum number of hosts is
+    /// configured, since Evergreen would otherwise run them one at a time on a single host.
+    ///
+    /// # Arguments
+    ///
+    /// * `gen_params` - Parameters for how the suite should be generated.
+    pub fn task_group(&self, gen_params: &ResmokeGenParams) -> Option<EvgTaskGroup> {
+        if !gen_params.use_task_group {
+            return None;
+        }
+        let n_sub_suites = self.sub_suites.len() as u64;
+        let max_hosts = gen_params
+            .task_group_max_hosts
+            .map_or(n_sub_suites, |max_hosts| max_hosts.min(n_sub_suites));
+        Some(EvgTaskGroup {
+            name: self.task_group_name(),
+            max_hosts: Some(max_hosts),
+            setup_group: Some(setup_commands(gen_params.require_multiversion_setup)),
+            setup_group_can_fail_task: Some(true),
+            teardown_group: Some(vec![fn_call("cleanup environment")]),
+            tasks: self.sub_suites.iter().map(|s| s.name.clone()).collect(),
+            ..Default::default()
+        })
     }
 
     pub fn display_task(&self) -> DisplayTask {
@@ -72,14 +202,51 @@ impl GeneratedSuite {
         }
     }
 
+    /// Name of the suite file holding the tests in none of the sub-suites.
+    pub fn misc_suite_name(&self) -> String {
+        misc_suite_name(&self.task_name, &self.build_variant)
+    }
+
     pub fn execution_tasks(&self, gen_params: &ResmokeGenParams) -> Vec<EvgTask> {
         self.sub_suites.iter().map(|s| s.task(gen_params)).collect()
     }
+
+    /// Reuse this split for another build variant in the same split family.
+    ///
+    /// # Arguments
+    ///
+    /// * `bv_name` - Build variant to name the sub-suites for.
+    pub fn for_build_variant(&self, bv_name: &str) -> GeneratedSuite {
+        GeneratedSuite {
+            sub_suites: self
+                .sub_suites
+                .iter()
+                .enumerate()
+                .map(|(i, s)| SubSuite {
+                    name: sub_suite_name(&self.task_name, i, bv_name),
+                    test_list: s.test_list.clone(),
+                })
+                .collect(),
+            build_variant: bv_name.to_string(),
+            ..self.clone()
+        }
+    }
+}
+
+/// How tests are divided into sub-suites.
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SplitStrategy {
+    /// Balance sub-suites by historic test runtime.
+    Runtime,
+    /// Balance sub-suites by number of tests.
+    TestCount,
 }
 
 #[derive(Debug, Clone)]
 pub struct SplitConfig {
     pub n_suites: usize,
+    pub strategy: SplitStrategy,
 }
 
 pub trait TaskSplitting: Send + Sync {
@@ -90,73 +257,175 @@ pub trait TaskSplitting: Send + Sync {
 pub struct TaskSplitter {
     pub test_discovery: Arc<dyn TestDiscovery>,
     pub split_config: SplitConfig,
+    pub generator_config: Arc<GeneratorConfig>,
+    /// Splits of a previous generation to reuse for tasks that have not changed.
+    pub previous_generation: Option<Arc<PreviousGeneration>>,
 }
 
 impl TaskSplitting for TaskSplitter {
     fn split_task(&self, task_stats: &TaskRuntimeHistory, bv_name: &str) -> GeneratedSuite {
         let suite_name = &task_stats.suite_name;
 
+        // The fingerprint covers the discovered tests and the test history, so both are needed
+        // even when the split of a previous generation ends up being reused.
+        let start = Instant::now();
         let test_list: Vec<String> = self
             .test_discovery
             .discover_tests(suite_name)
             .into_iter()
             .filter(|s| Path::new(s).exists())
             .collect();
+        let discovery_duration_ms = start.elapsed().as_millis() as u64;
 
-        let total_runtime = task_stats
-            .test_map
-            .iter()
-            .fold(0.0, |init, (_, item)| init + item.average_runtime);
-
-        let max_tasks = min(self.split_config.n_suites, test_list.len());
-        let runtime_per_subtask = total_runtime / max_tasks as f64;
-        event!(
-            Level::INFO,
-            "Splitting task: {}, runtime: {}, tests: {}",
-            &suite_name, runtime_per_subtask, test_list.len()
+        let split_config = self
+            .generator_config
+            .settings_for(&task_stats.task_name, suite_name, bv_name)
+            .split_config(&self.split_config);
+        let suite_config = ResmokeSuiteConfig::read_suite_config(suite_name);
+        let fingerprint = TaskFingerprint::new(
+            &test_list,
+            &suite_config.to_yaml_string(),
+            task_stats,
+            &split_config,
         );
-        let mut sub_suites = vec![];
-        let mut running_tests = vec![];
-        let mut running_runtime = 0.0;
-        let mut i = 0;
-        for test in test_list {
-            let test_name = get_test_name(&test);
-            if let Some(test_stats) = task_stats.test_map.get(&test_name) {
-                if (running_runtime + test_stats.average_runtime > runtime_per_subtask)
-                    && !running_tests.is_empty()
-                    && sub_suites.len() < max_tasks - 1
-                {
-                    sub_suites.push(SubSuite {
-                        name: format!("{}_{}_{}", &task_stats.task_name, i, bv_name),
-                        test_list: running_tests.clone(),
-                    });
-                    running_tests = vec![];
-                    running_runtime = 0.0;
-                    i += 1;
-                }
-                running_runtime += test_stats.average_runtime;
-            }
-            running_tests.push(test.clone());
-        }
-        if !running_tests.is_empty() {
-            sub_suites.push(SubSuite {
-                name: format!("{}_{}_{}", &task_stats.task_name, i, bv_name),
-                test_list: running_tests.clone(),
-            });
+        if let Some(previous) = self
+            .previous_generation
+            .as_ref()
+            .and_then(|p| p.unchanged_suite(&task_stats.task_name, bv_name, fingerprint))
+        {
+            event!(
+                Level::INFO,
+                task_name = task_stats.task_name.as_str(),
+                %fingerprint,
+                "Reusing split of unchanged task"
+            );
+            return GeneratedSuite {
+                discovery_duration_ms,
+                suite_config: Some(suite_config),
+                ..previous.clone()
+            };
         }
 
+        let sub_suites = match split_config.strategy {
+            SplitStrategy::Runtime => {
+                split_by_runtime(task_stats, test_list, split_config.n_suites, bv_name)
+            }
+            SplitStrategy::TestCount => split_by_test_count(
+                &task_stats.task_name,
+                test_list,
+                split_config.n_suites,
+                bv_name,
+            ),
+        };
+
         GeneratedSuite {
             task_name: task_stats.task_name.clone(),
             sub_suites,
             suite_name: suite_name.to_string(),
+            build_variant: bv_name.to_string(),
+            discovery_duration_ms,
+            fingerprint: Some(fingerprint),
+            suite_config: Some(suite_config),
         }
     }
 }
 
+/// Divide tests into sub-suites with roughly equal historic runtime.
+fn split_by_runtime(
+    task_stats: &TaskRuntimeHistory,
+    test_list: Vec<String>,
+    n_suites: usize,
+    bv_name: &str,
+) -> Vec<SubSuite> {
+    let suite_name = &task_stats.suite_name;
+    // Sum in a fixed order so the total (and the split) is the same on every run.
+    let mut test_runtimes: Vec<(&String, f64)> = task_stats
+        .test_map
+        .iter()
+        .map(|(name, item)| (name, item.average_runtime))
+        .collect();
+    test_runtimes.sort_by(|a, b| a.0.cmp(b.0));
+    let total_runtime = test_runtimes
+        .iter()
+        .fold(0.0, |init, (_, runtime)| init + runtime);
+
+    let max_tasks = min(n_suites, test_list.len());
+    let runtime_per_subtask = total_runtime / max_tasks as f64;
+    event!(
+        Level::INFO,
+        "Splitting task: {}, runtime: {}, tests: {}",
+        &suite_name,
+        runtime_per_subtask,
+        test_list.len()
+    );
+    let mut sub_suites = vec![];
+    let mut running_tests = vec![];
+    let mut running_runtime = 0.0;
+    let mut i = 0;
+    for test in test_list {
+        let test_name = get_test_name(&test);
+        if let Some(test_stats) = task_stats.test_map.get(&test_name) {
+            if (running_runtime + test_stats.average_runtime > runtime_per_subtask)
+                && !running_tests.is_empty()
+                && sub_suites.len() < max_tasks - 1
+            {
+                sub_suites.push(SubSuite {
+                    name: sub_suite_name(&task_stats.task_name, i, bv_name),
+                    test_list: running_tests.clone(),
+                });
+                running_tests = vec![];
+                running_runtime = 0.0;
+                i += 1;
+            }
+            running_runtime += test_stats.average_runtime;
+        }
+        running_tests.push(test.clone());
+    }
+    if !running_tests.is_empty() {
+        sub_suites.push(SubSuite {
+            name: sub_suite_name(&task_stats.task_name, i, bv_name),
+            test_list: running_tests.clone(),
+        });
+    }
+
+    sub_suites
+}
+
+/// Divide tests into sub-suites with roughly equal numbers of tests.
+fn split_by_test_count(
+    task_name: &str,
+    test_list: Vec<String>,
+    n_suites: usize,
+    bv_name: &str,
+) -> Vec<SubSuite> {
+    let max_tasks = min(n_suites, test_list.len());
+    if max_tasks == 0 {
+        return vec![];
+    }
+    let base_size = test_list.len() / max_tasks;
+    let remainder = test_list.len() % max_tasks;
+
+    let mut tests = test_list.into_iter();
+    (0..max_tasks)
+        .map(|i| {
+            let size = base_size + if i < remainder { 1 } else { 0 };
+            SubSuite {
+                name: sub_suite_name(task_name, i, bv_name),
+                test_list: tests.by_ref().take(size).collect(),
+            }
+        })
+        .collect()
+}
+
 fn resmoke_args(origin_suite: &str, params: &ResmokeGenParams) -> String {
-    format!("--originSuite={} {}", origin_suite, params.resmoke_args)
+    let mut args = format!("--originSuite={} {}", origin_suite, params.resmoke_args);
+    if params.repeat_suites > 1 {
+        args.push_str(&format!(" --repeatSuites={}", params.repeat_suites));
+    }
+    args
 }
 
+/// Dependencies of sub-tasks when neither the `_gen` task nor an override provide any.
 fn dependencies() -> Vec<TaskDependency> {
     vec![TaskDependency {
         name: String::from("archive_dist_test"),
@@ -164,11 +433,8 @@ fn dependencies() -> Vec<TaskDependency> {
     }]
 }
 
-fn resmoke_commands(
-    run_test_fn_name: &str,
-    run_test_vars: HashMap<String, ParamValue>,
-    requires_multiversion_setup: bool,
-) -> Vec<EvgCommand> {
+/// Commands that prepare a host to run resmoke, run once per group when tasks are grouped.
+fn setup_commands(requires_multiversion_setup: bool) -> Vec<EvgCommand> {
     let mut commands = vec![];
 
     if requires_multiversion_setup {
@@ -183,7 +449,6 @@ fn resmoke_commands(
         commands.push(fn_call("do multiversion setup"));
     }
 
-    commands.push(fn_call_with_params(run_test_fn_name, run_test_vars));
     commands
 }
 
@@ -211,3 +476,135 @@ fn run_test_vars(suite_file: &str, params: &ResmokeGenParams) -> HashMap<String,
 
     run_test_vars
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    #[test]
+    fn test_split_by_test_count_should_balance_test_counts() {
+        let test_list: Vec<String> = (0..7).map(|i| format!("jstests/t{}.js", i)).collect();
+
+        let sub_suites = split_by_test_count("task", test_list, 3, "bv");
+
+        assert_eq!(
+            sub_suites
+                .iter()
+                .map(|s| s.test_list.len())
+                .collect::<Vec<_>>(),
+            vec![3, 2, 2]
+        );
+        assert_eq!(sub_suites[2].name, "task_2_bv");
+        assert_eq!(
+            sub_suites[2].test_list,
+            vec!["jstests/t5.js", "jstests/t6.js"]
+        );
+    }
+
+    fn build_task_group_params(task_group_max_hosts: Option<u64>) -> ResmokeGenParams {
+        ResmokeGenParams {
+            use_large_distro: false,
+            large_distro_name: None,
+            require_multiversion_setup: false,
+            repeat_suites: 1,
+            resmoke_args: "".to_string(),
+            resmoke_jobs_max: None,
+            config_location: None,
+            exec_timeout_secs: None,
+            inherited_properties: InheritedTaskProperties::default(),
+            depends_on: None,
+            compile_variant: None,
+            use_task_group: true,
+            task_group_max_hosts,
+            activate: false,
+        }
+    }
+
+    #[test]
+    fn test_task_group_should_share_setup_between_sub_suites() {
+        let gen_suite = GeneratedSuite {
+            task_name: "task".to_string(),
+            suite_name: "suite".to_string(),
+            build_variant: "bv".to_string(),
+            sub_suites: split_by_test_count(
+                "task",
+                vec!["t0.js".to_string(), "t1.js".to_string()],
+                2,
+                "bv",
+            ),
+            discovery_duration_ms: 0,
+            fingerprint: None,
+            suite_config: None,
+        };
+        let gen_params = build_task_group_params(Some(2));
+
+        let task_group = gen_suite.task_group(&gen_params).unwrap();
+        let task_refs = gen_suite.task_refs(&gen_params);
+        let tasks = gen_suite.execution_tasks(&gen_params);
+
+        assert_eq!(task_group.name, "task_bv_TG");
+        assert_eq!(task_group.tasks, vec!["task_0_bv", "task_1_bv"]);
+        assert_eq!(task_group.max_hosts, Some(2));
+        assert_eq!(task_group.setup_group.unwrap().len(), 2);
+        assert_eq!(task_refs.len(), 1);
+        assert_eq!(task_refs[0].name, task_group.name);
+        assert!(tasks.iter().all(|t| t.commands.len() == 1));
+    }
+
+    #[rstest]
+    #[case(None, 3)]
+    #[case(Some(2), 2)]
+    #[case(Some(10), 3)]
+    fn test_task_group_max_hosts_should_default_to_sub_suite_count(
+        #[case] task_group_max_hosts: Option<u64>,
+        #[case] expected: u64,
+    ) {
+        let test_list: Vec<String> = (0..3).map(|i| format!("t{}.js", i)).collect();
+        let gen_suite = GeneratedSuite {
+            task_name: "task".to_string(),
+            suite_name: "suite".to_string(),
+            build_variant: "bv".to_string(),
+            sub_suites: split_by_test_count("task", test_list, 3, "bv"),
+            discovery_duration_ms: 0,
+            fingerprint: None,
+            suite_config: None,
+        };
+
+        let task_group = gen_suite
+            .task_group(&build_task_group_params(task_group_max_hosts))
+            .unwrap();
+
+        assert_eq!(task_group.max_hosts, Some(expected));
+    }
+
+    #[test]
+    fn test_reused_split_should_be_named_for_build_variant() {
+        let test_list: Vec<String> = (0..5).map(|i| format!("jstests/t{}.js", i)).collect();
+        let gen_suite = GeneratedSuite {
+            task_name: "task".to_string(),
+            suite_name: "suite".to_string(),
+            build_variant: "bv".to_string(),
+            sub_suites: split_by_test_count("task", test_list, 2, "bv"),
+            discovery_duration_ms: 0,
+            fingerprint: None,
+            suite_config: None,
+        };
+
+        let reused = gen_suite.for_build_variant("bv-required");
+
+        assert_eq!(reused.build_variant, "bv-required");
+        assert_eq!(
+            reused
+                .sub_suites
+                .iter()
+                .map(|s| s.name.as_str())
+                .collect::<Vec<_>>(),
+            vec!["task_0_bv-required", "task_1_bv-required"]
+        );
+        assert_eq!(
+            reused.sub_suites[0].test_list,
+            gen_suite.sub_suites[0].test_list
+        );
+    }
+}
@@ -0,0 +1,345 @@
+use std::collections::HashMap;
+
+use anyhow::{bail, Result};
+use serde::{Deserialize, Serialize};
+use shrub_rs::models::{
+    task::{EvgTask, TaskDependency},
+    variant::BuildVariant,
+};
+
+use crate::{expansions::Expansions, taskname::GEN_SUFFIX};
+
+/// Expansion naming the build variant that compiles the binaries a build variant tests.
+pub const COMPILE_VARIANT_EXPANSION: &str = "compile_variant";
+
+/// Properties of a `_gen` task that its generated sub-tasks inherit.
+///
+/// The following fields of the `_gen` task are never inherited:
+///
+/// * `name` - Sub-tasks are named after the sub-suite or fuzzer index they run.
+/// * `commands` - Sub-tasks run the generated tests instead of the generator.
+///
+/// Dependencies on other generator tasks are dropped from `depends_on`, since those have
+/// already run by the time sub-tasks are generated.
+///
+/// `priority` and `allowed_requesters` are not part of the project model, so they are
+/// inherited through [`ExtraTaskProperties`] instead.
+#[derive(Debug, Clone, Default)]
+pub struct InheritedTaskProperties {
+    pub depends_on: Option<Vec<TaskDependency>>,
+    pub tags: Option<Vec<String>>,
+    pub exec_timeout_secs: Option<u64>,
+    pub patchable: Option<bool>,
+    pub stepback: Option<bool>,
+}
+
+impl InheritedTaskProperties {
+    /// Collect the inheritable properties of a `_gen` task.
+    ///
+    /// # Arguments
+    ///
+    /// * `gen_task` - Definition of the `_gen` task.
+    pub fn from_gen_task(gen_task: &EvgTask) -> Self {
+        // Destructure every field so new fields have to be classified as inherited or not.
+        let EvgTask {
+            name: _,
+            commands: _,
+            depends_on,
+            exec_timeout_secs,
+            tags,
+            patchable,
+            stepback,
+        } = gen_task;
+
+        let depends_on = depends_on
+            .as_ref()
+            .map(|deps| {
+                deps.iter()
+                    .filter(|d| !d.name.ends_with(GEN_SUFFIX))
+                    .cloned()
+                    .collect::<Vec<_>>()
+            })
+            .filter(|deps| !deps.is_empty());
+
+        Self {
+            depends_on,
+            tags: tags.clone(),
+            exec_timeout_secs: *exec_timeout_secs,
+            patchable: *patchable,
+            stepback: *stepback,
+        }
+    }
+
+    /// Apply these properties to a generated sub-task.
+    ///
+    /// Properties the generator already set on the sub-task are kept.
+    ///
+    /// # Arguments
+    ///
+    /// * `sub_task` - Generated sub-task to update.
+    pub fn apply(&self, sub_task: &mut EvgTask) {
+        if sub_task.depends_on.is_none() {
+            sub_task.depends_on = self.depends_on.clone();
+        }
+        if sub_task.tags.is_none() {
+            sub_task.tags = self.tags.clone();
+        }
+        if sub_task.exec_timeout_secs.is_none() {
+            sub_task.exec_timeout_secs = self.exec_timeout_secs;
+        }
+        if sub_task.patchable.is_none() {
+            sub_task.patchable = self.patchable;
+        }
+        if sub_task.stepback.is_none() {
+            sub_task.stepback = self.stepback;
+        }
+    }
+}
+
+/// Properties of a `_gen` task its sub-tasks inherit that are not part of the project model.
+///
+/// These are read from the raw project configuration and added to the generated
+/// configuration when it is written.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExtraTaskProperties {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub priority: Option<i64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub allowed_requesters: Option<Vec<String>>,
+}
+
+impl ExtraTaskProperties {
+    /// Determine if no properties are set.
+    pub fn is_empty(&self) -> bool {
+        self.priority.is_none() && self.allowed_requesters.is_none()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct RawTask {
+    name: String,
+    #[serde(flatten)]
+    properties: ExtraTaskProperties,
+}
+
+#[derive(Debug, Deserialize)]
+struct RawProject {
+    #[serde(default)]
+    tasks: Vec<RawTask>,
+}
+
+/// Get the extra properties of each task in an evaluated project configuration.
+///
+/// Tasks without any extra properties are left out.
+///
+/// # Arguments
+///
+/// * `project_yaml` - Evaluated project configuration.
+pub fn extra_task_properties_from_yaml(
+    project_yaml: &str,
+) -> Result<HashMap<String, ExtraTaskProperties>> {
+    let project: RawProject = serde_yaml::from_str(project_yaml)?;
+    Ok(project
+        .tasks
+        .into_iter()
+        .filter(|t| !t.properties.is_empty())
+        .map(|t| (t.name, t.properties))
+        .collect())
+}
+
+/// Get the build variant that compiles the binaries a build variant tests.
+///
+/// Returns `None` if the build variant tests binaries it compiles itself. It is an error
+/// for the compile variant to not be part of the project.
+///
+/// # Arguments
+///
+/// * `build_variant` - Build variant sub-tasks are generated for.
+/// * `expansions` - Expansions available to the build variant.
+/// * `build_variant_map` - All build variants in the project.
+pub fn compile_variant(
+    build_variant: &BuildVariant,
+    expansions: &Expansions,
+    build_variant_map: &HashMap<String, &BuildVariant>,
+) -> Result<Option<String>> {
+    match expansions
+        .for_build_variant(build_variant)
+        .get(COMPILE_VARIANT_EXPANSION)
+    {
+        Some(compile_variant) if !build_variant_map.contains_key(compile_variant) => bail!(
+            "Compile variant '{}' of build variant '{}' is not in the project",
+            compile_variant,
+            build_variant.name
+        ),
+        compile_variant => Ok(compile_variant.map(|v| v.to_string())),
+    }
+}
+
+/// Point dependencies without an explicit build variant at the compile variant.
+///
+/// # Arguments
+///
+/// * `sub_task` - Generated sub-task to update.
+/// * `compile_variant` - Build variant that compiles the binaries the sub-task tests.
+pub fn set_compile_variant(sub_task: &mut EvgTask, compile_variant: Option<&str>) {
+    if let Some(compile_variant) = compile_variant {
+        for dependency in sub_task.depends_on.iter_mut().flatten() {
+            dependency
+                .variant
+                .get_or_insert_with(|| compile_variant.to_string());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maplit::hashmap;
+    use shrub_rs::models::commands::fn_call;
+
+    fn build_gen_task() -> EvgTask {
+        EvgTask {
+            name: "auth_gen".to_string(),
+            commands: vec![fn_call("generate resmoke tasks")],
+            depends_on: Some(vec![
+                TaskDependency {
+                    name: "version_gen".to_string(),
+                    variant: Some("generate-tasks".to_string()),
+                },
+                TaskDependency {
+                    name: "package".to_string(),
+                    variant: None,
+                },
+            ]),
+            exec_timeout_secs: Some(3600),
+            tags: Some(vec!["auth".to_string()]),
+            patchable: Some(false),
+            stepback: Some(true),
+        }
+    }
+
+    #[test]
+    fn test_sub_task_should_inherit_gen_task_properties() {
+        let properties = InheritedTaskProperties::from_gen_task(&build_gen_task());
+        let mut sub_task = EvgTask {
+            name: "auth_0".to_string(),
+            ..Default::default()
+        };
+
+        properties.apply(&mut sub_task);
+
+        assert_eq!(sub_task.name, "auth_0");
+        assert!(sub_task.commands.is_empty());
+        let depends_on: Vec<String> = sub_task
+            .depends_on
+            .unwrap()
+            .into_iter()
+            .map(|d| d.name)
+            .collect();
+        assert_eq!(depends_on, vec!["package".to_string()]);
+        assert_eq!(sub_task.tags, Some(vec!["auth".to_string()]));
+        assert_eq!(sub_task.exec_timeout_secs, Some(3600));
+        assert_eq!(sub_task.patchable, Some(false));
+        assert_eq!(sub_task.stepback, Some(true));
+    }
+
+    #[test]
+    fn test_only_generator_dependencies_should_not_be_inherited() {
+        let mut gen_task = build_gen_task();
+        gen_task.depends_on.as_mut().unwrap().pop();
+
+        let properties = InheritedTaskProperties::from_gen_task(&gen_task);
+
+        assert!(properties.depends_on.is_none());
+    }
+
+    #[test]
+    fn test_generated_properties_should_take_precedence() {
+        let properties = InheritedTaskProperties::from_gen_task(&build_gen_task());
+        let mut sub_task = EvgTask {
+            name: "auth_0".to_string(),
+            exec_timeout_secs: Some(60),
+            ..Default::default()
+        };
+
+        properties.apply(&mut sub_task);
+
+        assert_eq!(sub_task.exec_timeout_secs, Some(60));
+    }
+
+    #[test]
+    fn test_compile_variant_should_be_set_on_dependencies_without_variant() {
+        let mut sub_task = build_gen_task();
+
+        set_compile_variant(&mut sub_task, Some("compile"));
+
+        let variants: Vec<Option<String>> = sub_task
+            .depends_on
+            .unwrap()
+            .into_iter()
+            .map(|d| d.variant)
+            .collect();
+        assert_eq!(
+            variants,
+            vec![
+                Some("generate-tasks".to_string()),
+                Some("compile".to_string())
+            ]
+        );
+    }
+
+    #[test]
+    fn test_unknown_compile_variant_should_be_an_error() {
+        let compile = BuildVariant {
+            name: "compile".to_string(),
+            ..Default::default()
+        };
+        let build_variant = BuildVariant {
+            name: "tests".to_string(),
+            expansions: Some(hashmap! {
+                COMPILE_VARIANT_EXPANSION.to_string() => "compile".to_string(),
+            }),
+            ..Default::default()
+        };
+        let expansions = Expansions::default();
+
+        let with_compile = hashmap! { "compile".to_string() => &compile };
+        let without_compile = hashmap! { "tests".to_string() => &build_variant };
+
+        assert_eq!(
+            compile_variant(&build_variant, &expansions, &with_compile).unwrap(),
+            Some("compile".to_string())
+        );
+        assert!(compile_variant(&build_variant, &expansions, &without_compile).is_err());
+        assert_eq!(
+            compile_variant(&compile, &expansions, &with_compile).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_extra_task_properties_should_be_read_from_project() {
+        let properties = extra_task_properties_from_yaml(
+            r#"
+tasks:
+  - name: auth_gen
+    priority: 50
+    allowed_requesters: ["patch", "github_pr"]
+  - name: core_gen
+    commands:
+      - func: generate resmoke tasks
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            properties,
+            hashmap! {
+                "auth_gen".to_string() => ExtraTaskProperties {
+                    priority: Some(50),
+                    allowed_requesters: Some(vec!["patch".to_string(), "github_pr".to_string()]),
+                },
+            }
+        );
+    }
+}
@@ -1,5 +1,7 @@
-use anyhow::Result;
+use anyhow::{Context, Result};
+use async_trait::async_trait;
 use std::collections::HashMap;
+use std::sync::Arc;
 
 use maplit::hashmap;
 use shrub_rs::models::{
@@ -10,12 +12,28 @@ use shrub_rs::models::{
 };
 use tracing::{event, Level};
 
-use crate::{resmoke::ResmokeSuiteConfig, util::name_generated_task};
+use crate::{
+    gen_vars::FuzzerGenVars,
+    generator_functions::{GeneratorFunctions, TaskKind},
+    get_multiversion_filter,
+    report::TaskReport,
+    resmoke::{MultiversionSuite, ResmokeSuiteConfig, VersionCombinationConfig},
+    task_properties::{set_compile_variant, InheritedTaskProperties, COMPILE_VARIANT_EXPANSION},
+    task_types::task_type_generator::{GeneratedTask, TaskGenContext, TaskTypeGenerator},
+    taskname::remove_gen_suffix_ref,
+    util::name_generated_task,
+};
 
 #[derive(Debug)]
 pub struct FuzzerTask {
     pub task_name: String,
     pub sub_tasks: Vec<EvgTask>,
+    /// Mixed version suites the sub-tasks run against.
+    pub multiversion_suites: Vec<MultiversionSuite>,
+    /// Distros the sub-tasks should run on, if not the build variant default.
+    pub distros: Option<Vec<String>>,
+    /// Should the sub-tasks be activated.
+    pub activate: bool,
 }
 
 impl FuzzerTask {
@@ -29,7 +47,7 @@ impl FuzzerTask {
     pub fn build_task_ref(&self) -> Vec<TaskRef> {
         self.sub_tasks
             .iter()
-            .map(|s| s.get_reference(None, Some(false)))
+            .map(|s| s.get_reference(self.distros.clone(), Some(self.activate)))
             .collect()
     }
 }
@@ -66,12 +84,35 @@ pub struct FuzzerGenTaskParams {
     pub use_large_distro: Option<bool>,
     /// Name of large distro to generate.
     pub large_distro_name: Option<String>,
+    /// Execution timeout for each generated sub-task.
+    pub exec_timeout_secs: Option<u64>,
+    /// Properties of the `_gen` task sub-tasks inherit.
+    pub inherited_properties: InheritedTaskProperties,
+    /// Tasks sub-tasks depend on, overriding the dependencies of the `_gen` task.
+    pub depends_on: Option<Vec<TaskDependency>>,
+    /// Build variant that compiles the binaries sub-tasks test, if not their own.
+    pub compile_variant: Option<String>,
+    /// Should the sub-tasks be activated.
+    pub activate: bool,
     /// Location of generated task configuration.
     pub config_location: String,
+    /// Directory generated suite files are written to.
+    pub config_dir: String,
+    /// Old versions to restrict multiversion generation to, all versions if not specified.
+    pub multiversion_filter: Option<Vec<String>>,
     pub suite_config: ResmokeSuiteConfig,
 }
 
 impl FuzzerGenTaskParams {
+    /// Distros sub-tasks should run on, if not the build variant default.
+    pub fn distros(&self) -> Option<Vec<String>> {
+        if self.use_large_distro.unwrap_or(false) {
+            self.large_distro_name.clone().map(|d| vec![d])
+        } else {
+            None
+        }
+    }
+
     fn build_jstestfuzz_vars(&self) -> HashMap<String, ParamValue> {
         hashmap! {
             "npm_command".to_string() => ParamValue::from(self.npm_command.as_str()),
@@ -112,11 +153,14 @@ impl FuzzerGenTaskParams {
         vars
     }
 
-    pub fn get_version_combination(&self) -> Result<Vec<String>> {
+    pub fn get_version_combination(
+        &self,
+        version_combinations: &VersionCombinationConfig,
+    ) -> Result<Vec<String>> {
         Ok(self
             .suite_config
             .get_fixture_type()?
-            .get_version_combinations())
+            .get_version_combinations(version_combinations))
     }
 }
 
@@ -127,12 +171,14 @@ pub trait GenFuzzerService: Sync + Send {
 #[derive(Debug, Clone)]
 pub struct GenFuzzerServiceImpl {
     last_versions: Vec<String>,
+    version_combinations: VersionCombinationConfig,
 }
 
 impl GenFuzzerServiceImpl {
-    pub fn new(last_versions: &[String]) -> Self {
+    pub fn new(last_versions: &[String], version_combinations: VersionCombinationConfig) -> Self {
         Self {
             last_versions: last_versions.to_owned(),
+            version_combinations,
         }
     }
 
@@ -155,19 +201,29 @@ impl GenFuzzerService for GenFuzzerServiceImpl {
     fn generate_fuzzer_task(&self, params: &FuzzerGenTaskParams) -> Result<FuzzerTask> {
         let task_name = &params.task_name;
         let mut sub_tasks: Vec<EvgTask> = vec![];
+        let mut multiversion_suites = vec![];
         if params.require_multiversion_setup.unwrap_or(false) {
-            let version_combinations = &params.get_version_combination()?;
+            let version_combinations =
+                &params.get_version_combination(&self.version_combinations)?;
             event!(
                 Level::INFO,
                 task_name = task_name.as_str(),
                 "Generating multiversion fuzzer"
             );
-            for version in &self.last_versions {
+            let last_versions = self.last_versions.iter().filter(|v| {
+                params
+                    .multiversion_filter
+                    .as_ref()
+                    .map(|f| f.contains(v))
+                    .unwrap_or(true)
+            });
+            for version in last_versions {
                 for mixed_bin_version in version_combinations {
                     let base_task_name =
                         Self::build_name(&params.task_name, version, mixed_bin_version);
                     let base_suite_name =
                         Self::build_name(&params.suite, version, mixed_bin_version);
+                    let suite_file = format!("{}/{}.yml", params.config_dir, &base_suite_name);
 
                     sub_tasks.extend(
                         (0..params.num_tasks)
@@ -176,12 +232,17 @@ impl GenFuzzerService for GenFuzzerServiceImpl {
                                     &base_task_name,
                                     i,
                                     params,
-                                    Some(&base_suite_name),
+                                    Some(&suite_file),
                                     Some(mixed_bin_version),
                                 )
                             })
                             .collect::<Vec<EvgTask>>(),
                     );
+                    multiversion_suites.push(MultiversionSuite {
+                        name: base_suite_name,
+                        old_bin_version: version.to_string(),
+                        mixed_bin_versions: mixed_bin_version.to_string(),
+                    });
                 }
             }
         } else {
@@ -193,6 +254,109 @@ impl GenFuzzerService for GenFuzzerServiceImpl {
         Ok(FuzzerTask {
             task_name: params.task_name.to_string(),
             sub_tasks,
+            multiversion_suites,
+            distros: params.distros(),
+            activate: params.activate,
+        })
+    }
+}
+
+/// Generates the sub-tasks of fuzzer tasks.
+pub struct FuzzerTaskGenerator {
+    gen_fuzzer_service: Arc<dyn GenFuzzerService>,
+}
+
+impl FuzzerTaskGenerator {
+    /// Create a generator of fuzzer tasks.
+    ///
+    /// # Arguments
+    ///
+    /// * `gen_fuzzer_service` - Service to generate the sub-tasks of a fuzzer.
+    pub fn new(gen_fuzzer_service: Arc<dyn GenFuzzerService>) -> Self {
+        Self { gen_fuzzer_service }
+    }
+
+    fn fuzzer_params(task_def: &EvgTask, ctx: &TaskGenContext) -> Result<FuzzerGenTaskParams> {
+        let build_variant = &ctx.build_variant;
+        let expansions = ctx.expansions.for_build_variant(build_variant);
+        let generator_functions = ctx.generator_config.generator_functions();
+        let vars = FuzzerGenVars::from_task(task_def, generator_functions, &expansions)?;
+        let suite = generator_functions.find_expanded_suite_name(task_def, &expansions)?;
+        let suite_config = ResmokeSuiteConfig::try_read_suite_config(&suite)?;
+        let mut params = FuzzerGenTaskParams {
+            task_name: remove_gen_suffix_ref(&task_def.name).to_string(),
+            variant: build_variant.name.to_string(),
+            suite,
+            num_files: vars.num_files,
+            num_tasks: vars.num_tasks,
+            resmoke_args: vars.resmoke_args,
+            npm_command: vars.npm_command,
+            jstestfuzz_vars: vars.jstestfuzz_vars,
+            continue_on_failure: vars.continue_on_failure,
+            resmoke_jobs_max: vars.resmoke_jobs_max,
+            should_shuffle: vars.should_shuffle,
+            timeout_secs: vars.timeout_secs,
+            require_multiversion_setup: Some(
+                task_def
+                    .tags
+                    .clone()
+                    .unwrap_or_default()
+                    .contains(&"multiversion".to_string()),
+            ),
+            use_large_distro: vars.use_large_distro,
+            large_distro_name: expansions.get("large_distro_name").map(|d| d.to_string()),
+            exec_timeout_secs: None,
+            inherited_properties: InheritedTaskProperties::from_gen_task(task_def),
+            depends_on: None,
+            compile_variant: expansions
+                .get(COMPILE_VARIANT_EXPANSION)
+                .map(|v| v.to_string()),
+            activate: ctx.generator_config.activation_policy().activate_task(
+                ctx.requester,
+                remove_gen_suffix_ref(&task_def.name),
+                &build_variant.name,
+            ),
+            config_location: ctx.config_location.to_string(),
+            config_dir: ctx.suite_sink.config_dir().to_string(),
+            multiversion_filter: get_multiversion_filter(
+                task_def,
+                generator_functions,
+                &expansions,
+            )?,
+            suite_config,
+        };
+        ctx.generator_config
+            .settings_for(&params.task_name, &params.suite, &params.variant)
+            .apply_to_fuzzer(&mut params);
+        Ok(params)
+    }
+}
+
+#[async_trait]
+impl TaskTypeGenerator for FuzzerTaskGenerator {
+    fn detects(&self, task_def: &EvgTask, generator_functions: &GeneratorFunctions) -> bool {
+        generator_functions.task_kind(task_def) == Some(TaskKind::Fuzzer)
+    }
+
+    async fn generate(&self, task_def: &EvgTask, ctx: &TaskGenContext) -> Result<GeneratedTask> {
+        let params = Self::fuzzer_params(task_def, ctx).context("Failed to read fuzzer params")?;
+        let fuzzer_task = self
+            .gen_fuzzer_service
+            .generate_fuzzer_task(&params)
+            .context("Failed to generate fuzzer task")?;
+        ctx.suite_sink
+            .write_multiversion_suites(&params.suite_config, &fuzzer_task.multiversion_suites)
+            .await?;
+        ctx.generation_report
+            .lock()
+            .unwrap()
+            .add_task(TaskReport::from_fuzzer_task(&params.variant, &fuzzer_task));
+
+        Ok(GeneratedTask {
+            task_refs: fuzzer_task.build_task_ref(),
+            display_tasks: vec![fuzzer_task.build_display_task()],
+            tasks: fuzzer_task.sub_tasks,
+            ..Default::default()
         })
     }
 }
@@ -235,21 +399,95 @@ fn build_fuzzer_sub_task(
         ),
     ]);
 
-    EvgTask {
+    let mut sub_task = EvgTask {
         name: sub_task_name,
         commands,
-        depends_on: Some(vec![TaskDependency {
+        depends_on: params.depends_on.clone(),
+        exec_timeout_secs: params.exec_timeout_secs,
+        ..Default::default()
+    };
+    params.inherited_properties.apply(&mut sub_task);
+    sub_task.depends_on.get_or_insert_with(|| {
+        vec![TaskDependency {
             name: "archive_dist_test_debug".to_string(),
             variant: None,
-        }]),
-        ..Default::default()
-    }
+        }]
+    });
+    set_compile_variant(&mut sub_task, params.compile_variant.as_deref());
+    sub_task
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use rstest::*;
+    use std::str::FromStr;
+
+    fn build_multiversion_params(multiversion_filter: Option<Vec<String>>) -> FuzzerGenTaskParams {
+        let suite_config = ResmokeSuiteConfig::from_str(
+            "
+            test_kind: js_test
+            executor:
+              config: {}
+            ",
+        )
+        .unwrap();
+        FuzzerGenTaskParams {
+            task_name: "agg_fuzzer".to_string(),
+            variant: "bv".to_string(),
+            suite: "agg_fuzzer_suite".to_string(),
+            num_files: 5,
+            num_tasks: 2,
+            resmoke_args: "".to_string(),
+            npm_command: "jstestfuzz".to_string(),
+            jstestfuzz_vars: None,
+            continue_on_failure: false,
+            resmoke_jobs_max: 1,
+            should_shuffle: false,
+            timeout_secs: 1800,
+            require_multiversion_setup: Some(true),
+            use_large_distro: None,
+            large_distro_name: None,
+            exec_timeout_secs: None,
+            inherited_properties: InheritedTaskProperties::default(),
+            depends_on: None,
+            compile_variant: None,
+            activate: true,
+            config_location: "location".to_string(),
+            config_dir: "generated_resmoke_config".to_string(),
+            multiversion_filter,
+            suite_config,
+        }
+    }
+
+    // generate_fuzzer_task
+    #[rstest]
+    #[case(None, vec!["agg_fuzzer_suite_last_lts", "agg_fuzzer_suite_last_continuous"])]
+    #[case(Some(vec!["last_lts".to_string()]), vec!["agg_fuzzer_suite_last_lts"])]
+    #[case(Some(vec!["last_lts".to_string(), "last_continuous".to_string()]), vec!["agg_fuzzer_suite_last_lts", "agg_fuzzer_suite_last_continuous"])]
+    fn test_multiversion_filter_should_restrict_generated_versions(
+        #[case] multiversion_filter: Option<Vec<String>>,
+        #[case] expected_suites: Vec<&str>,
+    ) {
+        let service = GenFuzzerServiceImpl::new(
+            &["last_lts".to_string(), "last_continuous".to_string()],
+            VersionCombinationConfig::default(),
+        );
+        let params = build_multiversion_params(multiversion_filter);
+
+        let fuzzer_task = service.generate_fuzzer_task(&params).unwrap();
+
+        let suites: Vec<&str> = fuzzer_task
+            .multiversion_suites
+            .iter()
+            .map(|s| s.name.as_str())
+            .collect();
+        assert_eq!(suites, expected_suites);
+        assert_eq!(
+            fuzzer_task.sub_tasks.len(),
+            expected_suites.len() * params.num_tasks as usize
+        );
+    }
 
     // build_name
     #[rstest]
@@ -1 +1,3 @@
 pub mod fuzzer_tasks;
+pub mod resmoke_tasks;
+pub mod task_type_generator;
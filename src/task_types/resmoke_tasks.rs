@@ -0,0 +1,338 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Instant;
+
+use anyhow::{Context, Result};
+use async_trait::async_trait;
+use shrub_rs::models::task::EvgTask;
+use tokio::sync::{mpsc, oneshot};
+use tracing::{event, Level};
+
+use crate::{
+    fingerprint::PreviousGeneration,
+    gen_vars::ResmokeGenVars,
+    generator_functions::{GeneratorFunctions, TaskKind},
+    report::{TaskReport, TaskTimings},
+    split_tasks::{split_family, GeneratedSuite, ResmokeGenParams, TaskSplitting},
+    task_history::{TaskHistoryService, TaskRuntimeHistory},
+    task_properties::{InheritedTaskProperties, COMPILE_VARIANT_EXPANSION},
+    task_types::task_type_generator::{GeneratedTask, TaskGenContext, TaskTypeGenerator},
+    taskname::remove_gen_suffix_ref,
+};
+
+/// Tests of a task split into sub-suites, shared by the build variants in a split family.
+#[derive(Clone)]
+struct TaskSplit {
+    gen_suite: GeneratedSuite,
+    task_history: Arc<TaskRuntimeHistory>,
+    timings: TaskTimings,
+}
+
+/// Key of a split: the task name, suite name and split family.
+///
+/// Suite names are expanded per build variant, so variants in a split family only share a split
+/// when they also run the same suite.
+type SplitKey = (String, String, String);
+
+enum GenTaskMessage {
+    GetTask {
+        task_name: String,
+        suite_name: String,
+        bv_name: String,
+        split_family: String,
+        respond_to: oneshot::Sender<TaskSplit>,
+        sender: Arc<mpsc::Sender<GenTaskMessage>>,
+    },
+    AddTask {
+        key: SplitKey,
+        task_split: TaskSplit,
+    },
+}
+
+struct GenTaskActor {
+    receiver: mpsc::Receiver<GenTaskMessage>,
+    generated_tasks: HashMap<SplitKey, TaskSplit>,
+    waiting_tasks: HashMap<SplitKey, Vec<oneshot::Sender<TaskSplit>>>,
+
+    task_history_service: Arc<dyn TaskHistoryService>,
+    task_splitter: Arc<dyn TaskSplitting>,
+}
+
+impl GenTaskActor {
+    fn new(
+        receiver: mpsc::Receiver<GenTaskMessage>,
+        task_history_service: Arc<dyn TaskHistoryService>,
+        task_splitter: Arc<dyn TaskSplitting>,
+    ) -> Self {
+        GenTaskActor {
+            receiver,
+            generated_tasks: HashMap::new(),
+            waiting_tasks: HashMap::new(),
+
+            task_history_service,
+            task_splitter,
+        }
+    }
+
+    fn handle_message(&mut self, msg: GenTaskMessage) {
+        match msg {
+            GenTaskMessage::GetTask {
+                task_name,
+                suite_name,
+                bv_name,
+                split_family,
+                respond_to,
+                sender,
+            } => {
+                let key = (task_name.clone(), suite_name.clone(), split_family);
+                if let Some(task_split) = self.generated_tasks.get(&key) {
+                    let _ = respond_to.send(task_split.clone());
+                } else if let Some(waiting_tasks) = self.waiting_tasks.get_mut(&key) {
+                    waiting_tasks.push(respond_to);
+                } else {
+                    self.waiting_tasks.insert(key.clone(), vec![respond_to]);
+                    let task_history_service = self.task_history_service.clone();
+                    let ts = self.task_splitter.clone();
+
+                    tokio::spawn(async move {
+                        let task_name = task_name.as_str();
+                        let short_task_name = remove_gen_suffix_ref(task_name);
+                        let start = Instant::now();
+                        let task_history = task_history_service
+                            .get_task_history(short_task_name, &bv_name, &suite_name)
+                            .await;
+                        let history_lookup_ms = start.elapsed().as_millis() as u64;
+                        event!(
+                            Level::INFO,
+                            task_name,
+                            duration_sec = history_lookup_ms / 1000,
+                            "History looked up finished"
+                        );
+                        event!(Level::INFO, task_name, "Splitting Task");
+                        let start = Instant::now();
+                        let gen_suite = ts.split_task(&task_history, &bv_name);
+                        let split_duration_ms = start.elapsed().as_millis() as u64;
+                        event!(
+                            Level::INFO,
+                            task_name,
+                            duration_ms = split_duration_ms,
+                            "Split finished"
+                        );
+                        let timings = TaskTimings {
+                            discovery_ms: gen_suite.discovery_duration_ms,
+                            history_lookup_ms,
+                            split_ms: split_duration_ms
+                                .saturating_sub(gen_suite.discovery_duration_ms),
+                        };
+
+                        let msg = GenTaskMessage::AddTask {
+                            key,
+                            task_split: TaskSplit {
+                                gen_suite,
+                                task_history: Arc::new(task_history),
+                                timings,
+                            },
+                        };
+                        let _ = sender.send(msg).await;
+                    });
+                }
+            }
+            GenTaskMessage::AddTask { key, task_split } => {
+                self.generated_tasks.insert(key.clone(), task_split.clone());
+                if let Some(waiting_tasks) = self.waiting_tasks.get_mut(&key) {
+                    while let Some(sender) = waiting_tasks.pop() {
+                        let _ = sender.send(task_split.clone());
+                    }
+                }
+            }
+        }
+    }
+
+    async fn run(&mut self) {
+        while let Some(msg) = self.receiver.recv().await {
+            self.handle_message(msg);
+        }
+    }
+}
+
+#[derive(Clone)]
+struct GenTaskActorHandle {
+    sender: Arc<mpsc::Sender<GenTaskMessage>>,
+}
+
+impl GenTaskActorHandle {
+    fn new(
+        task_history_service: Arc<dyn TaskHistoryService>,
+        task_splitter: Arc<dyn TaskSplitting>,
+    ) -> Self {
+        let (sender, receiver) = mpsc::channel(128);
+        let mut actor = GenTaskActor::new(receiver, task_history_service, task_splitter);
+        tokio::spawn(async move { actor.run().await });
+
+        Self {
+            sender: Arc::new(sender),
+        }
+    }
+
+    /// Get the split of a task for a build variant.
+    ///
+    /// Tasks are split once per split family, using the first build variant to request it.
+    async fn get_task(
+        &self,
+        task_name: &str,
+        suite_name: &str,
+        bv_name: &str,
+        split_family: &str,
+    ) -> TaskSplit {
+        let (send, recv) = oneshot::channel();
+        let msg = GenTaskMessage::GetTask {
+            task_name: task_name.to_string(),
+            suite_name: suite_name.to_string(),
+            bv_name: bv_name.to_string(),
+            split_family: split_family.to_string(),
+            respond_to: send,
+            sender: self.sender.clone(),
+        };
+
+        let _ = self.sender.send(msg).await;
+        recv.await.unwrap()
+    }
+}
+
+/// Generates the sub-tasks of resmoke tasks by splitting their suite into sub-suites.
+///
+/// Each task is split once per split family, so a generator should only be used for a
+/// single generation.
+pub struct ResmokeTaskGenerator {
+    gen_task_actor: GenTaskActorHandle,
+    previous_generation: Option<Arc<PreviousGeneration>>,
+}
+
+impl ResmokeTaskGenerator {
+    /// Create a generator of resmoke tasks.
+    ///
+    /// # Arguments
+    ///
+    /// * `task_history_service` - Service to look up the runtime history of tests.
+    /// * `task_splitter` - Service to split tasks into sub-suites.
+    /// * `previous_generation` - Previous generation to reuse the suite files of unchanged
+    ///   splits from.
+    pub fn new(
+        task_history_service: Arc<dyn TaskHistoryService>,
+        task_splitter: Arc<dyn TaskSplitting>,
+        previous_generation: Option<Arc<PreviousGeneration>>,
+    ) -> Self {
+        Self {
+            gen_task_actor: GenTaskActorHandle::new(task_history_service, task_splitter),
+            previous_generation,
+        }
+    }
+
+    fn gen_params(task_def: &EvgTask, ctx: &TaskGenContext) -> Result<ResmokeGenParams> {
+        let build_variant = &ctx.build_variant;
+        let expansions = ctx.expansions.for_build_variant(build_variant);
+        let generator_functions = ctx.generator_config.generator_functions();
+        let vars = ResmokeGenVars::from_task(task_def, generator_functions, &expansions)?;
+        let suite_name = generator_functions.find_expanded_suite_name(task_def, &expansions)?;
+        let mut params = ResmokeGenParams {
+            use_large_distro: vars.use_large_distro,
+            large_distro_name: expansions.get("large_distro_name").map(|d| d.to_string()),
+            require_multiversion_setup: false,
+            repeat_suites: 1,
+            resmoke_args: vars.resmoke_args,
+            config_location: Some(ctx.config_location.to_string()),
+            resmoke_jobs_max: vars.resmoke_jobs_max,
+            exec_timeout_secs: None,
+            inherited_properties: InheritedTaskProperties::from_gen_task(task_def),
+            depends_on: None,
+            compile_variant: expansions
+                .get(COMPILE_VARIANT_EXPANSION)
+                .map(|v| v.to_string()),
+            activate: ctx.generator_config.activation_policy().activate_task(
+                ctx.requester,
+                remove_gen_suffix_ref(&task_def.name),
+                &build_variant.name,
+            ),
+            use_task_group: false,
+            task_group_max_hosts: None,
+        };
+        ctx.generator_config
+            .settings_for(
+                remove_gen_suffix_ref(&task_def.name),
+                &suite_name,
+                &build_variant.name,
+            )
+            .apply_to_resmoke(&mut params);
+        Ok(params)
+    }
+
+    /// Write the suite files of a split, copying them if the split is unchanged.
+    async fn write_suite_files(
+        &self,
+        gen_suite: &GeneratedSuite,
+        ctx: &TaskGenContext,
+    ) -> Result<()> {
+        match self
+            .previous_generation
+            .as_ref()
+            .filter(|p| p.is_unchanged(gen_suite))
+        {
+            Some(previous) => ctx.suite_sink.copy_suite_files(previous, gen_suite).await,
+            None => {
+                ctx.suite_sink.write_sub_suite(gen_suite).await;
+                Ok(())
+            }
+        }
+    }
+}
+
+#[async_trait]
+impl TaskTypeGenerator for ResmokeTaskGenerator {
+    fn detects(&self, task_def: &EvgTask, generator_functions: &GeneratorFunctions) -> bool {
+        generator_functions.task_kind(task_def) == Some(TaskKind::Resmoke)
+    }
+
+    async fn generate(&self, task_def: &EvgTask, ctx: &TaskGenContext) -> Result<GeneratedTask> {
+        let gen_params =
+            Self::gen_params(task_def, ctx).context("Failed to read resmoke params")?;
+        let bv_name = &ctx.build_variant.name;
+        let suite_name = ctx
+            .generator_config
+            .generator_functions()
+            .find_expanded_suite_name(
+                task_def,
+                &ctx.expansions.for_build_variant(&ctx.build_variant),
+            )?;
+        let split_family = split_family(&ctx.build_variant, &ctx.expansions);
+        let task_split = self
+            .gen_task_actor
+            .get_task(&task_def.name, &suite_name, bv_name, &split_family)
+            .await;
+
+        let gen_suite = task_split.gen_suite.for_build_variant(bv_name);
+        // Only the build variant the split was made for spent time on it.
+        let timings = if &task_split.gen_suite.build_variant == bv_name {
+            task_split.timings.clone()
+        } else {
+            TaskTimings::default()
+        };
+        ctx.generation_report
+            .lock()
+            .unwrap()
+            .add_task(TaskReport::from_generated_suite(
+                bv_name,
+                &gen_suite,
+                &task_split.task_history,
+                timings,
+            ));
+        self.write_suite_files(&gen_suite, ctx).await?;
+
+        Ok(GeneratedTask {
+            tasks: gen_suite.execution_tasks(&gen_params),
+            task_refs: gen_suite.task_refs(&gen_params),
+            display_tasks: vec![gen_suite.display_task()],
+            task_groups: gen_suite.task_group(&gen_params).into_iter().collect(),
+            generated_suite: Some(gen_suite),
+        })
+    }
+}
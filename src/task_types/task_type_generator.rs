@@ -0,0 +1,159 @@
+use std::sync::{Arc, Mutex};
+
+use anyhow::Result;
+use async_trait::async_trait;
+use shrub_rs::models::{
+    task::{EvgTask, TaskRef},
+    task_group::EvgTaskGroup,
+    variant::{BuildVariant, DisplayTask},
+};
+
+use crate::{
+    activation::Requester, expansions::Expansions, generator_config::GeneratorConfig,
+    generator_functions::GeneratorFunctions, report::GenerationReport, split_tasks::GeneratedSuite,
+    write_config::SuiteFileSink,
+};
+
+/// State shared by the generated tasks of a build variant.
+pub struct TaskGenContext {
+    /// Build variant tasks are generated for.
+    pub build_variant: BuildVariant,
+    /// Expansions available to the generated build variants.
+    pub expansions: Arc<Expansions>,
+    /// Location generated configuration is uploaded to.
+    pub config_location: String,
+    /// Configuration of how tasks are generated.
+    pub generator_config: Arc<GeneratorConfig>,
+    /// Requester of the version being generated.
+    pub requester: Requester,
+    /// Destination for the suite files of generated tasks.
+    pub suite_sink: Arc<dyn SuiteFileSink>,
+    /// Report to add generated tasks to.
+    pub generation_report: Arc<Mutex<GenerationReport>>,
+}
+
+/// Configuration generated for a single `_gen` task.
+#[derive(Debug, Default)]
+pub struct GeneratedTask {
+    /// Definitions of the generated sub-tasks.
+    pub tasks: Vec<EvgTask>,
+    /// References adding the sub-tasks to the build variant.
+    pub task_refs: Vec<TaskRef>,
+    /// Display tasks grouping the sub-tasks.
+    pub display_tasks: Vec<DisplayTask>,
+    /// Task groups the sub-tasks run in.
+    pub task_groups: Vec<EvgTaskGroup>,
+    /// Split of the task into sub-suites, if it was split.
+    pub generated_suite: Option<GeneratedSuite>,
+}
+
+/// Generator of the sub-tasks of one type of `_gen` task.
+#[async_trait]
+pub trait TaskTypeGenerator: Send + Sync {
+    /// Check if this generator generates the sub-tasks of a task.
+    ///
+    /// # Arguments
+    ///
+    /// * `task_def` - Definition of the task.
+    /// * `generator_functions` - Functions marking a task as generated.
+    fn detects(&self, task_def: &EvgTask, generator_functions: &GeneratorFunctions) -> bool;
+
+    /// Generate the sub-tasks of a task on a build variant.
+    ///
+    /// # Arguments
+    ///
+    /// * `task_def` - Definition of the task to generate.
+    /// * `ctx` - State shared by the generated tasks of the build variant.
+    async fn generate(&self, task_def: &EvgTask, ctx: &TaskGenContext) -> Result<GeneratedTask>;
+}
+
+/// Task type generators, checked in the order they were registered.
+#[derive(Clone, Default)]
+pub struct TaskTypeRegistry {
+    generators: Vec<Arc<dyn TaskTypeGenerator>>,
+}
+
+impl TaskTypeRegistry {
+    /// Add a generator, used for tasks no earlier generator detects.
+    pub fn register(&mut self, generator: Arc<dyn TaskTypeGenerator>) {
+        self.generators.push(generator);
+    }
+
+    /// Get the generator of a task, if any generator detects it.
+    ///
+    /// # Arguments
+    ///
+    /// * `task_def` - Definition of the task.
+    /// * `generator_functions` - Functions marking a task as generated.
+    pub fn find(
+        &self,
+        task_def: &EvgTask,
+        generator_functions: &GeneratorFunctions,
+    ) -> Option<Arc<dyn TaskTypeGenerator>> {
+        self.generators
+            .iter()
+            .find(|g| g.detects(task_def, generator_functions))
+            .cloned()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use shrub_rs::models::commands::{fn_call, EvgCommand};
+
+    struct FunctionGenerator {
+        function: String,
+    }
+
+    #[async_trait]
+    impl TaskTypeGenerator for FunctionGenerator {
+        fn detects(&self, task_def: &EvgTask, _generator_functions: &GeneratorFunctions) -> bool {
+            task_def
+                .commands
+                .iter()
+                .any(|c| matches!(c, EvgCommand::Function(f) if f.func == self.function))
+        }
+
+        async fn generate(
+            &self,
+            _task_def: &EvgTask,
+            _ctx: &TaskGenContext,
+        ) -> Result<GeneratedTask> {
+            Ok(GeneratedTask::default())
+        }
+    }
+
+    fn build_task(function: &str) -> EvgTask {
+        EvgTask {
+            name: "task_gen".to_string(),
+            commands: vec![fn_call(function)],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_first_registered_generator_should_be_found() {
+        let benchmark: Arc<dyn TaskTypeGenerator> = Arc::new(FunctionGenerator {
+            function: "generate benchmark tasks".to_string(),
+        });
+        let mut registry = TaskTypeRegistry::default();
+        registry.register(benchmark.clone());
+        registry.register(Arc::new(FunctionGenerator {
+            function: "generate benchmark tasks".to_string(),
+        }));
+        let generator_functions = GeneratorFunctions::default();
+
+        let found = registry
+            .find(
+                &build_task("generate benchmark tasks"),
+                &generator_functions,
+            )
+            .unwrap();
+
+        assert!(Arc::ptr_eq(&found, &benchmark));
+        assert!(registry
+            .find(&build_task("run tests"), &generator_functions)
+            .is_none());
+    }
+}
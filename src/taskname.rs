@@ -1,4 +1,4 @@
-const GEN_SUFFIX: &str = "_gen";
+pub const GEN_SUFFIX: &str = "_gen";
 
 pub fn name_sub_suite(
     parent_name: &str,
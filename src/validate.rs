@@ -0,0 +1,296 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{Display, Formatter};
+
+use shrub_rs::models::{commands::EvgCommand, params::ParamValue, project::EvgProject};
+
+/// An inconsistency found in a generated Evergreen configuration.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationError {
+    /// A display task references an execution task not in its build variant.
+    UnknownExecutionTask {
+        build_variant: String,
+        display_task: String,
+        task: String,
+    },
+    /// A task is defined more than once.
+    DuplicateTask { task: String },
+    /// A build variant references a task or task group that is not defined.
+    UndefinedTask { build_variant: String, task: String },
+    /// A task group contains a task that is not defined.
+    UndefinedGroupTask { task_group: String, task: String },
+    /// A task references a generated suite file that does not exist.
+    MissingSuiteFile { task: String, suite_file: String },
+}
+
+impl Display for ValidationError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownExecutionTask {
+                build_variant,
+                display_task,
+                task,
+            } => write!(
+                f,
+                "{}: display task '{}' references unknown execution task '{}'",
+                build_variant, display_task, task
+            ),
+            Self::DuplicateTask { task } => write!(f, "task '{}' is defined more than once", task),
+            Self::UndefinedTask {
+                build_variant,
+                task,
+            } => write!(f, "{}: task '{}' is not defined", build_variant, task),
+            Self::UndefinedGroupTask { task_group, task } => write!(
+                f,
+                "task group '{}' contains undefined task '{}'",
+                task_group, task
+            ),
+            Self::MissingSuiteFile { task, suite_file } => write!(
+                f,
+                "task '{}' references missing suite file '{}'",
+                task, suite_file
+            ),
+        }
+    }
+}
+
+/// Check a generated Evergreen configuration for inconsistencies.
+///
+/// All problems found are returned so they can be reported together.
+///
+/// # Arguments
+///
+/// * `project` - Generated configuration to validate.
+/// * `suite_file_exists` - Check if a suite file referenced by a task was written.
+pub fn validate_generated_config(
+    project: &EvgProject,
+    suite_file_exists: impl Fn(&str) -> bool,
+) -> Vec<ValidationError> {
+    let mut errors = vec![];
+
+    let mut task_counts: HashMap<&str, usize> = HashMap::new();
+    for task in &project.tasks {
+        *task_counts.entry(&task.name).or_insert(0) += 1;
+    }
+    let mut duplicates: Vec<&str> = task_counts
+        .iter()
+        .filter(|(_, count)| **count > 1)
+        .map(|(name, _)| *name)
+        .collect();
+    duplicates.sort_unstable();
+    errors.extend(
+        duplicates
+            .into_iter()
+            .map(|task| ValidationError::DuplicateTask {
+                task: task.to_string(),
+            }),
+    );
+
+    let task_group_map: HashMap<&str, &Vec<String>> = project
+        .task_groups
+        .iter()
+        .flatten()
+        .map(|tg| (tg.name.as_str(), &tg.tasks))
+        .collect();
+    for task_group in project.task_groups.iter().flatten() {
+        for task in &task_group.tasks {
+            if !task_counts.contains_key(task.as_str()) {
+                errors.push(ValidationError::UndefinedGroupTask {
+                    task_group: task_group.name.clone(),
+                    task: task.clone(),
+                });
+            }
+        }
+    }
+
+    for build_variant in &project.buildvariants {
+        let mut variant_tasks: HashSet<&str> = HashSet::new();
+        for task_ref in &build_variant.tasks {
+            match task_group_map.get(task_ref.name.as_str()) {
+                Some(group_tasks) => variant_tasks.extend(group_tasks.iter().map(|t| t.as_str())),
+                None => {
+                    variant_tasks.insert(task_ref.name.as_str());
+                }
+            }
+        }
+        for task_ref in &build_variant.tasks {
+            if !task_counts.contains_key(task_ref.name.as_str())
+                && !task_group_map.contains_key(task_ref.name.as_str())
+            {
+                errors.push(ValidationError::UndefinedTask {
+                    build_variant: build_variant.name.clone(),
+                    task: task_ref.name.clone(),
+                });
+            }
+        }
+
+        for display_task in build_variant.display_tasks.iter().flatten() {
+            for task in &display_task.execution_tasks {
+                if !variant_tasks.contains(task.as_str()) {
+                    errors.push(ValidationError::UnknownExecutionTask {
+                        build_variant: build_variant.name.clone(),
+                        display_task: display_task.name.clone(),
+                        task: task.clone(),
+                    });
+                }
+            }
+        }
+    }
+
+    for task in &project.tasks {
+        for command in &task.commands {
+            if let EvgCommand::Function(func) = command {
+                if let Some(ParamValue::String(suite)) =
+                    func.vars.as_ref().and_then(|v| v.get("suite"))
+                {
+                    if is_suite_file(suite) && !suite_file_exists(suite) {
+                        errors.push(ValidationError::MissingSuiteFile {
+                            task: task.name.clone(),
+                            suite_file: suite.clone(),
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    errors
+}
+
+fn is_suite_file(suite: &str) -> bool {
+    suite.ends_with(".yml") || suite.ends_with(".yaml")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use maplit::hashmap;
+    use shrub_rs::models::{
+        commands::fn_call_with_params,
+        task::{EvgTask, TaskRef},
+        task_group::EvgTaskGroup,
+        variant::{BuildVariant, DisplayTask},
+    };
+    use std::path::Path;
+
+    fn build_task(name: &str, suite: &str) -> EvgTask {
+        EvgTask {
+            name: name.to_string(),
+            commands: vec![fn_call_with_params(
+                "run generated tests",
+                hashmap! {
+                    "suite".to_string() => ParamValue::from(suite),
+                },
+            )],
+            ..Default::default()
+        }
+    }
+
+    fn build_variant(name: &str, tasks: &[&str], display_tasks: Vec<DisplayTask>) -> BuildVariant {
+        BuildVariant {
+            name: name.to_string(),
+            tasks: tasks
+                .iter()
+                .map(|t| TaskRef {
+                    name: t.to_string(),
+                    distros: None,
+                    activate: Some(false),
+                })
+                .collect(),
+            display_tasks: Some(display_tasks),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_valid_config_should_have_no_errors() {
+        let project = EvgProject {
+            buildvariants: vec![build_variant(
+                "bv",
+                &["task_0", "task_1"],
+                vec![DisplayTask {
+                    name: "task".to_string(),
+                    execution_tasks: vec!["task_0".to_string(), "task_1".to_string()],
+                }],
+            )],
+            tasks: vec![build_task("task_0", "suite"), build_task("task_1", "suite")],
+            ..Default::default()
+        };
+
+        assert_eq!(
+            validate_generated_config(&project, |suite| Path::new(suite).exists()),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_all_inconsistencies_should_be_reported() {
+        let project = EvgProject {
+            buildvariants: vec![build_variant(
+                "bv",
+                &["task_0", "task_2"],
+                vec![DisplayTask {
+                    name: "task".to_string(),
+                    execution_tasks: vec!["task_0".to_string(), "task_1".to_string()],
+                }],
+            )],
+            tasks: vec![
+                build_task("task_0", "suite"),
+                build_task("task_0", "generated_resmoke_config/does_not_exist.yml"),
+            ],
+            ..Default::default()
+        };
+
+        let errors = validate_generated_config(&project, |suite| Path::new(suite).exists());
+
+        assert_eq!(
+            errors,
+            vec![
+                ValidationError::DuplicateTask {
+                    task: "task_0".to_string()
+                },
+                ValidationError::UndefinedTask {
+                    build_variant: "bv".to_string(),
+                    task: "task_2".to_string()
+                },
+                ValidationError::UnknownExecutionTask {
+                    build_variant: "bv".to_string(),
+                    display_task: "task".to_string(),
+                    task: "task_1".to_string()
+                },
+                ValidationError::MissingSuiteFile {
+                    task: "task_0".to_string(),
+                    suite_file: "generated_resmoke_config/does_not_exist.yml".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_task_groups_should_be_validated() {
+        let project = EvgProject {
+            buildvariants: vec![build_variant(
+                "bv",
+                &["task_TG"],
+                vec![DisplayTask {
+                    name: "task".to_string(),
+                    execution_tasks: vec!["task_0".to_string(), "task_1".to_string()],
+                }],
+            )],
+            tasks: vec![build_task("task_0", "suite")],
+            task_groups: Some(vec![EvgTaskGroup {
+                name: "task_TG".to_string(),
+                tasks: vec!["task_0".to_string(), "task_1".to_string()],
+                ..Default::default()
+            }]),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            validate_generated_config(&project, |suite| Path::new(suite).exists()),
+            vec![ValidationError::UndefinedGroupTask {
+                task_group: "task_TG".to_string(),
+                task: "task_1".to_string()
+            }]
+        );
+    }
+}
@@ -0,0 +1,208 @@
+use std::collections::HashMap;
+
+use anyhow::Result;
+use regex::Regex;
+use serde::Deserialize;
+
+use crate::taskname::remove_gen_suffix_ref;
+
+/// Regex prioritizing build variants when no priority list is given.
+pub const DEFAULT_VARIANT_PRIORITY: &str = "-required$";
+
+#[derive(Debug, Deserialize)]
+struct TaggedBuildVariant {
+    name: String,
+    #[serde(default)]
+    tags: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct TaggedProject {
+    #[serde(default)]
+    buildvariants: Vec<TaggedBuildVariant>,
+}
+
+/// Get the tags of each build variant in an evaluated project configuration.
+///
+/// # Arguments
+///
+/// * `project_yaml` - Evaluated project configuration.
+pub fn variant_tags_from_yaml(project_yaml: &str) -> Result<HashMap<String, Vec<String>>> {
+    let project: TaggedProject = serde_yaml::from_str(project_yaml)?;
+    Ok(project
+        .buildvariants
+        .into_iter()
+        .map(|bv| (bv.name, bv.tags))
+        .collect())
+}
+
+fn any_match(patterns: &[Regex], value: &str) -> bool {
+    patterns.iter().any(|p| p.is_match(value))
+}
+
+fn included(include: &[Regex], exclude: &[Regex], values: &[&str]) -> bool {
+    (include.is_empty() || values.iter().any(|v| any_match(include, v)))
+        && !values.iter().any(|v| any_match(exclude, v))
+}
+
+/// Selects which build variants and tasks are generated, and in what order.
+///
+/// An empty include list includes everything. Excludes are checked after includes, so
+/// anything matching an exclude is left out even if it also matches an include.
+#[derive(Debug, Clone, Default)]
+pub struct VariantFilter {
+    /// Regexes matched against build variant names to include.
+    pub include_variants: Vec<Regex>,
+    /// Regexes matched against build variant names to exclude.
+    pub exclude_variants: Vec<Regex>,
+    /// Regexes matched against build variant tags to include.
+    pub include_tags: Vec<Regex>,
+    /// Regexes matched against build variant tags to exclude.
+    pub exclude_tags: Vec<Regex>,
+    /// Regexes matched against task names, without the `_gen` suffix, to include.
+    pub include_tasks: Vec<Regex>,
+    /// Regexes matched against task names, without the `_gen` suffix, to exclude.
+    pub exclude_tasks: Vec<Regex>,
+    /// Build variants matching earlier regexes are generated first.
+    pub priority: Vec<Regex>,
+}
+
+impl VariantFilter {
+    /// Check if a build variant should be generated.
+    ///
+    /// # Arguments
+    ///
+    /// * `build_variant` - Name of the build variant.
+    /// * `tags` - Tags of the build variant.
+    pub fn includes_build_variant(&self, build_variant: &str, tags: &[String]) -> bool {
+        let tags: Vec<&str> = tags.iter().map(|t| t.as_str()).collect();
+        included(
+            &self.include_variants,
+            &self.exclude_variants,
+            &[build_variant],
+        ) && included(&self.include_tags, &self.exclude_tags, &tags)
+    }
+
+    /// Check if a task should be generated.
+    ///
+    /// # Arguments
+    ///
+    /// * `task_name` - Name of the task, with or without the `_gen` suffix.
+    pub fn includes_task(&self, task_name: &str) -> bool {
+        included(
+            &self.include_tasks,
+            &self.exclude_tasks,
+            &[remove_gen_suffix_ref(task_name)],
+        )
+    }
+
+    /// Order build variants by priority, keeping the given order within a priority.
+    ///
+    /// Build variants matching none of the priority regexes come last.
+    ///
+    /// # Arguments
+    ///
+    /// * `build_variants` - Names of build variants to order.
+    pub fn prioritize(&self, build_variants: &mut [String]) {
+        build_variants.sort_by_key(|bv| {
+            self.priority
+                .iter()
+                .position(|p| p.is_match(bv))
+                .unwrap_or(self.priority.len())
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rstest::rstest;
+
+    fn regexes(patterns: &[&str]) -> Vec<Regex> {
+        patterns.iter().map(|p| Regex::new(p).unwrap()).collect()
+    }
+
+    #[rstest]
+    #[case("linux-required", &[], true)]
+    #[case("linux-debug", &[], false)]
+    #[case("linux-required", &["experimental"], false)]
+    #[case("windows", &[], false)]
+    fn test_includes_build_variant(
+        #[case] build_variant: &str,
+        #[case] tags: &[&str],
+        #[case] expected: bool,
+    ) {
+        let filter = VariantFilter {
+            include_variants: regexes(&["^linux"]),
+            exclude_variants: regexes(&["-debug$"]),
+            exclude_tags: regexes(&["^experimental$"]),
+            ..Default::default()
+        };
+        let tags: Vec<String> = tags.iter().map(|t| t.to_string()).collect();
+
+        assert_eq!(
+            filter.includes_build_variant(build_variant, &tags),
+            expected
+        );
+    }
+
+    #[test]
+    fn test_untagged_build_variants_should_not_match_tag_includes() {
+        let filter = VariantFilter {
+            include_tags: regexes(&["^required$"]),
+            ..Default::default()
+        };
+
+        assert!(filter.includes_build_variant("linux", &["required".to_string()]));
+        assert!(!filter.includes_build_variant("windows", &[]));
+    }
+
+    #[rstest]
+    #[case("auth_gen", true)]
+    #[case("auth_audit_gen", false)]
+    #[case("core", false)]
+    fn test_includes_task(#[case] task_name: &str, #[case] expected: bool) {
+        let filter = VariantFilter {
+            include_tasks: regexes(&["^auth"]),
+            exclude_tasks: regexes(&["audit$"]),
+            ..Default::default()
+        };
+
+        assert_eq!(filter.includes_task(task_name), expected);
+    }
+
+    #[test]
+    fn test_build_variants_should_be_ordered_by_priority() {
+        let filter = VariantFilter {
+            priority: regexes(&["^windows", DEFAULT_VARIANT_PRIORITY]),
+            ..Default::default()
+        };
+        let mut build_variants: Vec<String> = vec!["a", "b-required", "windows", "c-required"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+
+        filter.prioritize(&mut build_variants);
+
+        assert_eq!(
+            build_variants,
+            vec!["windows", "b-required", "c-required", "a"]
+        );
+    }
+
+    #[test]
+    fn test_variant_tags_should_be_read_from_project() {
+        let tags = variant_tags_from_yaml(
+            r#"
+buildvariants:
+  - name: linux
+    tags: ["required"]
+  - name: windows
+"#,
+        )
+        .unwrap();
+
+        assert_eq!(tags["linux"], vec!["required".to_string()]);
+        assert!(tags["windows"].is_empty());
+    }
+}
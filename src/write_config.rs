@@ -1,13 +1,63 @@
-use std::path::PathBuf;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc, Mutex,
+};
 
+use anyhow::{Context, Result};
+use async_trait::async_trait;
 use rayon::prelude::*;
 use tokio::sync::{mpsc, oneshot};
 
-use crate::{resmoke::ResmokeSuiteConfig, split_tasks::GeneratedSuite};
+use crate::{
+    fingerprint::PreviousGeneration,
+    resmoke::{MultiversionSuite, ResmokeSuiteConfig},
+    split_tasks::GeneratedSuite,
+};
+
+/// Destination for the resmoke suite files of generated tasks.
+#[async_trait]
+pub trait SuiteFileSink: Send + Sync {
+    /// Write the suite files of the sub-suites of a split task.
+    async fn write_sub_suite(&self, gen_suite: &GeneratedSuite);
+
+    /// Write the suite files of multiversion suites.
+    ///
+    /// Fails if the suite cannot be run against a mix of binary versions.
+    async fn write_multiversion_suites(
+        &self,
+        suite_config: &ResmokeSuiteConfig,
+        suites: &[MultiversionSuite],
+    ) -> Result<()>;
+
+    /// Copy the suite files of a split that is unchanged since a previous generation.
+    async fn copy_suite_files(
+        &self,
+        previous_generation: &PreviousGeneration,
+        gen_suite: &GeneratedSuite,
+    ) -> Result<()>;
+
+    /// Wait for all suite files to be written.
+    async fn flush(&self);
+
+    /// Directory suite files are written to, as referenced by generated tasks.
+    fn config_dir(&self) -> &str;
+
+    /// Check if a suite file referenced by a generated task has been written or copied by this
+    /// generation.
+    ///
+    /// # Arguments
+    ///
+    /// * `suite_file` - Path of the suite file, as referenced by the generated task.
+    fn has_suite_file(&self, suite_file: &str) -> bool;
+}
 
 #[derive(Debug)]
 enum WriteConfigMessage {
     SuiteFiles(GeneratedSuite),
+    /// Names and contents of multiversion suite files.
+    MultiversionSuiteFiles(Vec<(String, String)>),
     Flush(oneshot::Sender<()>),
 }
 
@@ -34,7 +84,9 @@ impl WriteConfigActor {
     fn handle_message(&mut self, msg: WriteConfigMessage) {
         match msg {
             WriteConfigMessage::SuiteFiles(gen_suite) => {
-                let base_config = ResmokeSuiteConfig::read_suite_config(&gen_suite.suite_name);
+                let base_config = gen_suite.suite_config.clone().unwrap_or_else(|| {
+                    ResmokeSuiteConfig::read_suite_config(&gen_suite.suite_name)
+                });
 
                 gen_suite.sub_suites.par_iter().for_each(|s| {
                     let config = base_config.update_config(&s.test_list, None);
@@ -51,9 +103,17 @@ impl WriteConfigActor {
                     .collect();
                 let misc_config = base_config.update_config(&[], Some(&all_tests));
                 let mut path = PathBuf::from(&self.config_dir);
-                path.push(format!("{}_misc.yml", gen_suite.task_name));
+                path.push(format!("{}.yml", gen_suite.misc_suite_name()));
                 std::fs::write(path, misc_config).unwrap();
             }
+            WriteConfigMessage::MultiversionSuiteFiles(suite_files) => {
+                suite_files.par_iter().for_each(|(name, config)| {
+                    let mut path = PathBuf::from(&self.config_dir);
+                    path.push(format!("{}.yml", name));
+
+                    std::fs::write(path, config).unwrap();
+                });
+            }
             WriteConfigMessage::Flush(sender) => sender.send(()).unwrap(),
         }
     }
@@ -62,7 +122,14 @@ impl WriteConfigActor {
 #[derive(Clone, Debug)]
 pub struct WriteConfigActorHandle {
     senders: Vec<mpsc::Sender<WriteConfigMessage>>,
-    index: usize,
+    index: Arc<AtomicUsize>,
+    config_dir: String,
+    /// File names of the suite files written or copied by this generation.
+    ///
+    /// Files left in the config directory by an earlier run are not included. Every build variant
+    /// running a fuzzer asks for the same multiversion suites, they are only written the first
+    /// time.
+    suite_files: Arc<Mutex<HashSet<String>>>,
 }
 
 impl WriteConfigActorHandle {
@@ -78,21 +145,77 @@ impl WriteConfigActorHandle {
                 tokio::spawn(async move { actor.run().await });
             });
 
-        Self { senders, index: 0 }
+        Self {
+            senders,
+            index: Arc::new(AtomicUsize::new(0)),
+            config_dir: config_dir.to_string(),
+            suite_files: Arc::new(Mutex::new(HashSet::new())),
+        }
+    }
+
+    /// Record the suite files of the sub-suites of a split task as part of this generation.
+    fn record_sub_suite(&self, gen_suite: &GeneratedSuite) {
+        let mut suite_files = self.suite_files.lock().unwrap();
+        for s in &gen_suite.sub_suites {
+            suite_files.insert(suite_file_name(&s.name));
+        }
+        suite_files.insert(suite_file_name(&gen_suite.misc_suite_name()));
     }
 
-    async fn round_robbin(&mut self, msg: WriteConfigMessage) {
-        let next = self.index;
-        self.index = (next + 1) % self.senders.len();
+    async fn round_robbin(&self, msg: WriteConfigMessage) {
+        let next = self.index.fetch_add(1, Ordering::Relaxed) % self.senders.len();
         self.senders[next].send(msg).await.unwrap();
     }
+}
 
-    pub async fn write_sub_suite(&mut self, gen_suite: &GeneratedSuite) {
+#[async_trait]
+impl SuiteFileSink for WriteConfigActorHandle {
+    async fn write_sub_suite(&self, gen_suite: &GeneratedSuite) {
+        self.record_sub_suite(gen_suite);
         let msg = WriteConfigMessage::SuiteFiles(gen_suite.clone());
         self.round_robbin(msg).await;
     }
 
-    pub async fn flush(&mut self) {
+    async fn write_multiversion_suites(
+        &self,
+        suite_config: &ResmokeSuiteConfig,
+        suites: &[MultiversionSuite],
+    ) -> Result<()> {
+        let suites: Vec<&MultiversionSuite> = {
+            let mut written = self.suite_files.lock().unwrap();
+            suites
+                .iter()
+                .filter(|s| written.insert(suite_file_name(&s.name)))
+                .collect()
+        };
+        if suites.is_empty() {
+            return Ok(());
+        }
+        let suite_files = suites
+            .into_iter()
+            .map(|s| {
+                let config = suite_config
+                    .update_multiversion_config(&s.old_bin_version, &s.mixed_bin_versions)
+                    .with_context(|| format!("Could not create multiversion suite '{}'", s.name))?;
+                Ok((s.name.clone(), config))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let msg = WriteConfigMessage::MultiversionSuiteFiles(suite_files);
+        self.round_robbin(msg).await;
+        Ok(())
+    }
+
+    async fn copy_suite_files(
+        &self,
+        previous_generation: &PreviousGeneration,
+        gen_suite: &GeneratedSuite,
+    ) -> Result<()> {
+        previous_generation.copy_suite_files(gen_suite, Path::new(&self.config_dir))?;
+        self.record_sub_suite(gen_suite);
+        Ok(())
+    }
+
+    async fn flush(&self) {
         for sender in &self.senders {
             let (send, recv) = oneshot::channel();
             let msg = WriteConfigMessage::Flush(send);
@@ -100,4 +223,75 @@ impl WriteConfigActorHandle {
             recv.await.unwrap();
         }
     }
+
+    fn config_dir(&self) -> &str {
+        &self.config_dir
+    }
+
+    fn has_suite_file(&self, suite_file: &str) -> bool {
+        let suite_file = Path::new(suite_file);
+        if suite_file.parent() != Some(Path::new(&self.config_dir)) {
+            return false;
+        }
+        suite_file
+            .file_name()
+            .and_then(|file_name| file_name.to_str())
+            .map(|file_name| self.suite_files.lock().unwrap().contains(file_name))
+            .unwrap_or(false)
+    }
+}
+
+/// Name of the file a suite is written to in the config directory.
+fn suite_file_name(suite_name: &str) -> String {
+    format!("{}.yml", suite_name)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::split_tasks::SubSuite;
+
+    use super::*;
+
+    fn build_gen_suite(sub_suite_names: &[&str]) -> GeneratedSuite {
+        GeneratedSuite {
+            task_name: "my_task".to_string(),
+            suite_name: "my_suite".to_string(),
+            build_variant: "my_variant".to_string(),
+            sub_suites: sub_suite_names
+                .iter()
+                .map(|name| SubSuite {
+                    name: name.to_string(),
+                    test_list: vec![],
+                })
+                .collect(),
+            discovery_duration_ms: 0,
+            fingerprint: None,
+            suite_config: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_has_suite_file_should_ignore_files_from_earlier_runs() {
+        let tmp_dir = tempfile::tempdir().unwrap();
+        let config_dir = tmp_dir.path().to_str().unwrap();
+        std::fs::write(tmp_dir.path().join("my_task_0.yml"), "stale").unwrap();
+        std::fs::write(tmp_dir.path().join("my_task_1.yml"), "stale").unwrap();
+        let sink = WriteConfigActorHandle::new(config_dir);
+
+        sink.record_sub_suite(&build_gen_suite(&["my_task_0"]));
+
+        assert!(sink.has_suite_file(&format!("{}/my_task_0.yml", config_dir)));
+        assert!(!sink.has_suite_file(&format!("{}/my_task_1.yml", config_dir)));
+    }
+
+    #[tokio::test]
+    async fn test_has_suite_file_should_only_look_in_config_dir() {
+        let sink = WriteConfigActorHandle::new("generated_resmoke_config");
+
+        sink.record_sub_suite(&build_gen_suite(&["my_task_0"]));
+
+        assert!(sink.has_suite_file("generated_resmoke_config/my_task_0.yml"));
+        assert!(!sink.has_suite_file("other_dir/my_task_0.yml"));
+        assert!(!sink.has_suite_file("my_task_0.yml"));
+    }
 }